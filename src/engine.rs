@@ -1,41 +1,385 @@
-use crate::features::animation::Animation;
+#[cfg(feature = "online")]
 use crate::features::websocket::{ChannelMessage, WebSocketWrapper};
+#[cfg(feature = "dev-console")]
+use crate::rpg::dev_console::DevCommand;
+use crate::svg::backend::sandbox::SandboxBackend;
+use crate::svg::backend::DomBackend;
+use crate::utils::console_log;
+use clock::Clock;
+use error::{EngineError, ErrorHandler};
+use event_bus::{EventBus, GameEvent};
+use held_input::HeldInputs;
 use input::Input;
-use scene::Scene;
-use state::State;
+use inspection::{InspectGame, StateSnapshot, WebSocketSnapshot};
+use js_sys::{Function, JSON};
+use scene::{Scene, SceneRegistry};
+use session::{SessionEvent, SessionLog};
+use state::{SceneTransition, State};
+use std::rc::Rc;
+use time_travel::TimeTravel;
 use wasm_bindgen::prelude::wasm_bindgen;
-use wasm_bindgen_test::console_log;
+use wasm_bindgen::JsValue;
 
 pub mod application_types;
+pub mod catalog;
 pub mod choice;
+pub mod clock;
+pub mod error;
+pub mod event_bus;
+pub mod held_input;
 pub mod input;
+pub mod inspection;
+pub mod key_bindings;
 pub mod scene;
+pub mod session;
 pub mod state;
+pub mod time_travel;
 
 #[wasm_bindgen]
 pub struct Engine {
-    pub(crate) scenes: Vec<Scene>,
-    pub(crate) web_socket_wrapper: WebSocketWrapper,
+    pub(crate) scenes: SceneRegistry,
+    #[cfg(feature = "online")]
+    pub(crate) web_socket_wrapper: Option<WebSocketWrapper>,
     pub(crate) shared_state: State,
+    pub(crate) session_log: SessionLog,
+    pub(crate) event_bus: EventBus,
+    pub(crate) error_handlers: Vec<ErrorHandler>,
+    pub(crate) held_inputs: HeldInputs,
+    // 押されたままのキーと、押した時に割り当てられていた入力
+    pub(crate) pressed_keys: Vec<(String, Input)>,
+    // 最後に animate に渡された step
+    pub(crate) last_step: f64,
+    pub(crate) time_travel: TimeTravel,
+    pub(crate) inspect_game: Option<InspectGame>,
+    pub(crate) clock: Clock,
+    // 歩行中などに押されたキー (終わってから処理する、最後の 1 つだけ)
+    pub(crate) buffered_input: Option<Input>,
+    // 最後に記録したフレームが何も起きない状態から始まったか
+    pub(crate) idle_frame_logged: bool,
 }
 
 #[wasm_bindgen]
 impl Engine {
-    pub(crate) fn new(
-        shared_state: State,
-        scenes: Vec<Scene>,
-        web_socket_wrapper: WebSocketWrapper,
-    ) -> Engine {
+    pub(crate) fn new(mut shared_state: State, scenes: SceneRegistry) -> Engine {
+        if let Err(error) = scenes.validate() {
+            shared_state.report_error(error);
+        }
+        let session_log = SessionLog::new(
+            shared_state.seed,
+            shared_state.elements.document.get_storage_item("save"),
+        );
+        let time_travel = TimeTravel::new(&shared_state, &scenes);
         Engine {
             scenes,
             shared_state,
-            web_socket_wrapper,
+            #[cfg(feature = "online")]
+            web_socket_wrapper: None,
+            session_log,
+            event_bus: EventBus::new(),
+            error_handlers: vec![],
+            held_inputs: HeldInputs::new(),
+            pressed_keys: vec![],
+            last_step: 0.0,
+            buffered_input: None,
+            idle_frame_logged: false,
+            time_travel,
+            inspect_game: None,
+            clock: Clock::new(),
         }
     }
 
+    pub fn seed(&self) -> u64 {
+        self.shared_state.seed
+    }
+
+    pub fn export_session_log(&self) -> String {
+        self.session_log.to_json()
+    }
+
+    // デバッグ用に現在の状態を JSON で返す (書き換えはできない)
+    pub fn inspect_state(&self) -> String {
+        self.snapshot().to_json()
+    }
+
+    // callback には GameEvent を JSON にしたオブジェクトが渡される
+    pub fn subscribe(&mut self, callback: Function) {
+        self.event_bus.subscribe(Box::new(move |event| {
+            let result = serde_json::to_string(event)
+                .map_err(|error| JsValue::from_str(&error.to_string()))
+                .and_then(|json| JSON::parse(&json))
+                .and_then(|event| callback.call1(&JsValue::NULL, &event));
+            // 購読者の失敗で他の購読者やゲームを止めない
+            if let Err(error) = result {
+                console_log!("event subscriber failed: {:?}", error);
+            }
+        }));
+    }
+
+    // callback には {kind, detail, message} のオブジェクトが渡される
+    // 登録がなければコンソールに出力するのみで、処理は続行する
+    pub fn set_error_callback(&mut self, callback: Function) {
+        self.on_error(move |error| {
+            let result = serde_json::to_value(error)
+                .map_err(|error| JsValue::from_str(&error.to_string()))
+                .and_then(|mut payload| {
+                    payload["message"] = error.to_string().into();
+                    JSON::parse(&payload.to_string())
+                })
+                .and_then(|payload| callback.call1(&JsValue::NULL, &payload));
+            if let Err(callback_error) = result {
+                console_log!("error callback failed: {:?} ({})", callback_error, error);
+            }
+        });
+    }
+
     pub fn keydown(&mut self, key: String) {
-        let input = Input::from(key);
+        let input = self.shared_state.key_bindings.resolve(&key);
+        let was_held = self.held_inputs.is_held(&input);
+        self.consume_input(input.clone());
+        if !was_held && self.held_inputs.is_held(&input) {
+            self.pressed_keys.push((key, input));
+        }
+    }
+
+    // 割り当てを引き直さず、押した時の入力を離す (押している間に割り当てを変えても離せるように)
+    pub fn keyup(&mut self, key: String) {
+        if let Some(index) = self
+            .pressed_keys
+            .iter()
+            .position(|(pressed_key, _)| *pressed_key == key)
+        {
+            let (_, input) = self.pressed_keys.remove(index);
+            self.release_input(input);
+        }
+    }
+
+    // ウィンドウがフォーカスを失うと keyup が届かないので、押されたままのキーをすべて離す
+    pub fn release_all_keys(&mut self) {
+        for input in self.held_inputs.release_all() {
+            self.session_log.push(SessionEvent::Keyup(input));
+        }
+        self.pressed_keys.clear();
+        self.shared_state.held_inputs.clear();
+    }
+
+    // 矢印キーを押しっぱなしにした時、delay ミリ秒後から rate ミリ秒ごとに入力し直す
+    pub fn set_key_repeat(&mut self, delay: f64, rate: f64) {
+        self.held_inputs.repeat_delay = delay;
+        self.held_inputs.repeat_rate = rate;
+    }
+
+    // メッセージの 1 文字あたりのミリ秒 (0 なら一度に表示する)
+    pub fn set_text_speed(&mut self, ms_per_char: f64) {
+        self.shared_state.references.borrow_mut().text_speed = ms_per_char.max(0.0);
+    }
+
+    // action は "Enter" "Cancel" "Context" "ArrowUp" などの Input 名
+    // 変更したキー割り当てはセーブ時にセーブデータと一緒に保存される
+    pub fn bind_key(&mut self, action: String, key: String) {
+        match Input::from_name(&action) {
+            Ok(input) => self.shared_state.key_bindings.bind(input, &key),
+            Err(error) => self.shared_state.report_error(error),
+        }
+        self.report_errors();
+    }
+
+    pub fn unbind_key(&mut self, key: String) {
+        self.shared_state.key_bindings.unbind(&key);
+    }
+
+    pub fn set_keys(&mut self, action: String, keys: Vec<String>) {
+        match Input::from_name(&action) {
+            Ok(input) => self.shared_state.key_bindings.set_keys(input, keys),
+            Err(error) => self.shared_state.report_error(error),
+        }
+        self.report_errors();
+    }
+
+    pub fn export_key_bindings(&self) -> String {
+        self.shared_state.key_bindings.to_json()
+    }
+
+    // 表示中のマークアップはすぐ書き換え、メッセージ・選択肢は次に描画する時から切り替わる
+    pub fn set_locale(&mut self, locale: String) {
+        let result = self.shared_state.catalog.borrow_mut().set_locale(&locale);
+        match result {
+            Ok(()) => self.apply_catalog(),
+            Err(error) => self.shared_state.report_error(error),
+        }
+        self.report_errors();
+    }
+
+    pub fn locale(&self) -> String {
+        self.shared_state.catalog.borrow().locale().to_string()
+    }
+
+    // {"ja": {...}, "en": {...}} 形式の JSON を読み込み、既存の文言に上書き・追加する
+    pub fn load_catalog(&mut self, json: String) {
+        let result = self.shared_state.catalog.borrow_mut().merge_json(&json);
+        match result {
+            Ok(()) => self.apply_catalog(),
+            Err(error) => self.shared_state.report_error(error),
+        }
+        self.report_errors();
+    }
+
+    // 一時停止中はアニメーション・通信・入力を止める (タブが隠れた時などにホストから呼ぶ)
+    // 押されたままのキーは離されたものとして扱う
+    pub fn pause(&mut self) {
+        if !self.clock.pause() {
+            return;
+        }
+        self.release_all_keys();
+        if let Some(pause) = &self.shared_state.elements.pause {
+            pause.show();
+        }
+    }
+
+    // 一時停止していた時間はアニメーションの経過時間に含めない
+    pub fn resume(&mut self) {
+        if !self.clock.resume() {
+            return;
+        }
+        if let Some(pause) = &self.shared_state.elements.pause {
+            pause.hide();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.clock.is_paused()
+    }
+
+    // keydown ごとに記録したスナップショットを古い順に返す
+    // [{id, event_index, step, input, state}] の state は inspect_state と同じ形式
+    pub fn list_snapshots(&self) -> String {
+        self.time_travel.to_json()
+    }
+
+    // 指定したスナップショットの keydown を処理する直前まで戻して描画し直す
+    // 作り直したエンジンにセッションログを途中まで再生させ、購読者と接続を引き継ぐ
+    // 以降の入力はその時点からの新しいログとして記録される
+    pub fn rewind(&mut self, snapshot_id: usize) {
+        let event_index = match self.time_travel.get(snapshot_id) {
+            Some(snapshot) => snapshot.event_index,
+            None => {
+                self.shared_state
+                    .report_error(EngineError::UnknownSnapshot(snapshot_id));
+                self.report_errors();
+                return;
+            }
+        };
+        let Some(remount) = self.time_travel.remount.clone() else {
+            self.shared_state
+                .report_error(EngineError::RewindUnavailable(snapshot_id));
+            self.report_errors();
+            return;
+        };
+        let mut session_log = self.session_log.clone();
+        session_log.events.truncate(event_index);
+        let document = self.time_travel.document.clone();
+        if let Err(error) = self.time_travel.restore_markups(&document) {
+            self.shared_state.report_error(error);
+            self.report_errors();
+            return;
+        }
+        // 再生中のセーブ・ロードでプレイヤーのセーブデータを読み書きしないよう、
+        // 記録開始時のセーブデータだけを置いた保存先で作り直す (key_bindings は後で今のものに揃える)
+        let sandbox = Rc::new(SandboxBackend::new(document.clone()));
+        if let Some(initial_save) = &session_log.initial_save {
+            sandbox.set_storage_item("save", initial_save);
+        }
+        let mut engine = remount(sandbox.clone());
+        engine.held_inputs.repeat_delay = self.held_inputs.repeat_delay;
+        engine.held_inputs.repeat_rate = self.held_inputs.repeat_rate;
+        engine.shared_state.references.borrow_mut().text_speed =
+            self.shared_state.references.borrow().text_speed;
+        // ロケールの切り替えもログに残らないので、再生前に今の文言に揃える
+        *engine.shared_state.catalog.borrow_mut() = self.shared_state.catalog.borrow().clone();
+        engine.apply_catalog();
+        if let Err(error) = engine.replay(&session_log) {
+            self.shared_state.report_error(error);
+            self.report_errors();
+            return;
+        }
+        sandbox.pass_through();
+        engine.time_travel.document = document;
+        engine.clock = self.clock.clone();
+        // キー割り当ての変更はログに残らないので、今の割り当てを使い続ける
+        engine.shared_state.key_bindings = self.shared_state.key_bindings.clone();
+        engine.time_travel.initial_markups = std::mem::take(&mut self.time_travel.initial_markups);
+        engine.event_bus = std::mem::take(&mut self.event_bus);
+        engine.error_handlers = std::mem::take(&mut self.error_handlers);
+        #[cfg(feature = "online")]
+        {
+            engine.web_socket_wrapper = self.web_socket_wrapper.take();
+        }
+        engine.sync_channel_membership();
+        *self = engine;
+    }
+
+    fn apply_catalog(&self) {
+        self.shared_state
+            .catalog
+            .borrow()
+            .apply(&self.shared_state.elements.document);
+    }
+
+    fn consume_input(&mut self, input: Input) {
+        if self.clock.is_paused() {
+            return;
+        }
+        if HeldInputs::is_holdable(&input) {
+            if !self.held_inputs.press(input.clone(), self.last_step) {
+                return;
+            }
+            self.shared_state.held_inputs = self.held_inputs.held();
+        }
+        self.time_travel.record(
+            self.session_log.events.len(),
+            self.last_step,
+            input.clone(),
+            self.snapshot(),
+        );
+        self.session_log.push(SessionEvent::Keydown(input.clone()));
+        self.handle_input(input);
+        self.dispatch_events();
+        self.report_errors();
+    }
+
+    fn release_input(&mut self, input: Input) {
+        if !self.held_inputs.is_held(&input) {
+            return;
+        }
+        self.session_log.push(SessionEvent::Keyup(input.clone()));
+        self.held_inputs.release(&input, self.last_step);
+        self.pressed_keys.retain(|(_, pressed)| *pressed != input);
+        self.shared_state.held_inputs = self.held_inputs.held();
+    }
+
+    // リピート入力はセッションログに残さない (再生時は animate から再度発生する)
+    fn repeat_held_input(&mut self, step: f64) {
+        if let Some(input) = self.held_inputs.due(step) {
+            // メッセージ表示中や画面遷移のアニメーション中は、解除されるまで待つ
+            if self.shared_state.references.borrow_mut().has_block_message
+                || self.has_animation_blocking_scene_update()
+            {
+                return;
+            }
+            self.held_inputs.repeated(step);
+            self.handle_input(input);
+        }
+    }
+
+    fn handle_input(&mut self, input: Input) {
         if self.shared_state.references.borrow_mut().has_block_message {
+            // 表示途中なら残りを出すだけで、次のメッセージには進まない
+            if self.shared_state.references.borrow_mut().revealing_message {
+                self.shared_state
+                    .references
+                    .borrow_mut()
+                    .complete_message_requested = true;
+                return;
+            }
             if !self
                 .shared_state
                 .references
@@ -44,163 +388,382 @@ impl Engine {
             {
                 self.shared_state.elements.message.hide();
             }
-            (*self.shared_state.references.borrow_mut()).has_block_message = false;
+            self.shared_state.references.borrow_mut().has_block_message = false;
             return;
         }
         if self.has_animation_blocking_scene_update() {
+            if self.shared_state.interrupt_animations.is_buffering_input() {
+                self.buffered_input = Some(input);
+                return;
+            }
             console_log!("keydown interrupt {:?}", input);
             return;
         }
-        let scene_index = self.shared_state.primitives.scene_index;
-        let consume_func = self.scenes[scene_index].consume_func;
-        console_log!("consume start scene: {:?}", scene_index);
-        consume_func(&mut self.scenes[scene_index], &mut self.shared_state, input);
+        let scene_id = self.shared_state.primitives.scene_id().to_owned();
+        console_log!("consume start scene: {:?}", scene_id);
+        self.with_scene(&scene_id, |scene, shared_state| {
+            scene.consume_input(shared_state, input)
+        });
         if !self.has_animation_blocking_scene_update() {
-            if self.shared_state.primitives.scene_index
-                != self.shared_state.primitives.requested_scene_index
-            {
-                self.shared_state.primitives.scene_index =
-                    self.shared_state.primitives.requested_scene_index;
-                self.on_scene_update();
+            self.apply_requested_updates();
+        }
+    }
+
+    // シーンが要求したシーン遷移・マップ移動を反映する
+    fn apply_requested_updates(&mut self) {
+        if let Some(transition) = self
+            .shared_state
+            .primitives
+            .requested_scene_transition
+            .take()
+        {
+            self.on_scene_update(transition);
+        }
+        if self.shared_state.primitives.map_index
+            != self.shared_state.primitives.requested_map_index
+        {
+            let from_map_index = self.shared_state.primitives.map_index;
+            self.shared_state.primitives.map_index =
+                self.shared_state.primitives.requested_map_index;
+            self.on_map_update(from_map_index);
+        }
+    }
+
+    fn on_scene_update(&mut self, transition: SceneTransition) {
+        let from_scene_id = self.shared_state.primitives.scene_id().to_owned();
+        // 不正な遷移は無視して、今のシーンに留まる
+        let error = match &transition {
+            SceneTransition::Switch(scene_id) | SceneTransition::Push(scene_id) => {
+                match self.scenes.get(&from_scene_id) {
+                    // next_scene_ids に宣言していない遷移は認めない
+                    Ok(from_scene) if !from_scene.next_scene_ids().contains(&scene_id.as_str()) => {
+                        Some(EngineError::InvalidSceneTransition(format!(
+                            "scene `{}` does not declare `{}` in next_scene_ids",
+                            from_scene_id, scene_id
+                        )))
+                    }
+                    Ok(_) => self.scenes.get(scene_id).err(),
+                    Err(error) => Some(error),
+                }
             }
-            if self.shared_state.primitives.map_index
-                != self.shared_state.primitives.requested_map_index
-            {
-                self.shared_state.primitives.map_index =
-                    self.shared_state.primitives.requested_map_index;
-                self.on_map_update();
+            SceneTransition::Pop if self.shared_state.primitives.scene_stack.len() < 2 => {
+                Some(EngineError::InvalidSceneTransition(format!(
+                    "cannot pop the bottom scene `{}`",
+                    from_scene_id
+                )))
+            }
+            SceneTransition::Pop => None,
+        };
+        if let Some(error) = error {
+            self.shared_state.report_error(error);
+            return;
+        }
+        match transition {
+            SceneTransition::Switch(scene_id) => {
+                // メニューからタイトルなどもあるので、スタックに積まれているシーンを一括で隠す
+                let stacked_scene_ids =
+                    std::mem::take(&mut self.shared_state.primitives.scene_stack);
+                for stacked_scene_id in stacked_scene_ids.iter() {
+                    self.with_scene(stacked_scene_id, |scene, _| scene.hide());
+                }
+                self.shared_state
+                    .primitives
+                    .scene_stack
+                    .push(scene_id.to_owned());
+                self.with_scene(&scene_id, |scene, shared_state| scene.init(shared_state));
+            }
+            SceneTransition::Push(scene_id) => {
+                // 下のシーンは隠さずに一時停止させる
+                self.with_scene(&from_scene_id, |scene, shared_state| {
+                    scene.pause(shared_state)
+                });
+                self.shared_state
+                    .primitives
+                    .scene_stack
+                    .push(scene_id.to_owned());
+                self.with_scene(&scene_id, |scene, shared_state| scene.init(shared_state));
+            }
+            SceneTransition::Pop => {
+                self.shared_state.primitives.scene_stack.pop();
+                self.with_scene(&from_scene_id, |scene, _| scene.hide());
+                let resumed_scene_id = self.shared_state.primitives.scene_id().to_owned();
+                self.with_scene(&resumed_scene_id, |scene, shared_state| {
+                    scene.resume(shared_state)
+                });
             }
         }
+        let scene_id = self.shared_state.primitives.scene_id().to_owned();
+        console_log!("scene_updated {:?}", scene_id);
+        self.shared_state.emit(GameEvent::SceneChanged {
+            from: from_scene_id,
+            to: scene_id,
+        });
+        self.sync_channel_membership();
     }
 
-    fn on_scene_update(&mut self) {
-        console_log!(
-            "scene_updated {:?}",
-            self.shared_state.primitives.scene_index
-        );
-        let scene_index = self.shared_state.primitives.scene_index;
-        if scene_index != 0 && !self.web_socket_wrapper.state.borrow_mut().is_joined {
-            self.web_socket_wrapper.join();
-        }
-        if scene_index == 0 && self.web_socket_wrapper.state.borrow_mut().is_joined {
-            self.web_socket_wrapper.left();
+    fn on_map_update(&mut self, from_map_index: usize) {
+        self.shared_state.emit(GameEvent::MapChanged {
+            from: from_map_index,
+            to: self.shared_state.primitives.map_index,
+        });
+        let scene_id = self.shared_state.primitives.scene_id().to_owned();
+        self.with_scene(&scene_id, |scene, shared_state| {
+            scene.on_map_update(shared_state)
+        });
+    }
+
+    // 登録されていないシーンはエラーとして通知し、何もしない
+    fn with_scene(&mut self, scene_id: &str, f: impl FnOnce(&mut dyn Scene, &mut State)) {
+        match self.scenes.get_mut(scene_id) {
+            Ok(scene) => f(scene, &mut self.shared_state),
+            Err(error) => self.shared_state.report_error(error),
         }
-        if !self.scenes[scene_index].is_partial_scene {
-            // メニューからタイトルなどもあるので遷移先が is_partial_scene でないなら一括で隠す
-            for scene in self.scenes.iter() {
-                scene.hide();
+    }
+
+    fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot::new(
+            &self.shared_state,
+            self.web_socket_snapshot(),
+            self.inspect_game.as_ref(),
+        )
+    }
+
+    fn report_errors(&mut self) {
+        for error in std::mem::take(&mut self.shared_state.errors).iter() {
+            console_log!("engine error: {}", error);
+            for error_handler in self.error_handlers.iter_mut() {
+                error_handler(error);
             }
         }
-        let init_func = self.scenes[scene_index].init_func;
-        init_func(&mut self.scenes[scene_index], &mut self.shared_state);
     }
 
-    fn on_map_update(&mut self) {
-        let update_map_func = self.scenes[self.shared_state.primitives.scene_index].update_map_func;
-        update_map_func(
-            &mut self.scenes[self.shared_state.primitives.scene_index],
-            &mut self.shared_state,
-        );
+    fn dispatch_events(&mut self) {
+        let events = std::mem::take(&mut self.shared_state.emitted_events);
+        self.event_bus.dispatch(events);
     }
 
     fn has_animation_blocking_scene_update(&self) -> bool {
-        self.shared_state
+        self.shared_state.interrupt_animations.is_blocking()
+    }
+
+    // step は requestAnimationFrame のタイムスタンプ (ミリ秒)
+    pub fn animate(&mut self, step: f64) {
+        if let Some(step) = self.clock.tick(step) {
+            self.advance(step);
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        let primitives = &self.shared_state.primitives;
+        self.buffered_input.is_none()
+            && self.held_inputs.held().is_empty()
+            && primitives.requested_scene_transition.is_none()
+            && primitives.map_index == primitives.requested_map_index
+            && self.shared_state.to_send_channel_messages.is_empty()
+            && self.shared_state.interrupt_animations.is_steady()
+    }
+
+    // セッションログには一時停止分を差し引いた step を記録する
+    fn advance(&mut self, step: f64) {
+        // 何も起きない状態から始まったフレームは、次のフレームで置き換えても同じ状態になる
+        // 放置している間にセッションログ (巻き戻しの再生) が伸び続けないよう、最後の 1 つだけ残す
+        if !(self.idle_frame_logged && self.session_log.replace_last_animate(step)) {
+            self.session_log.push(SessionEvent::Animate(step));
+        }
+        self.idle_frame_logged = self.is_idle();
+        self.last_step = step;
+        self.flush_channel_messages();
+
+        let references = self.shared_state.references.clone();
+        let callbacks = self
+            .shared_state
             .interrupt_animations
-            .iter()
-            .find(|animation| animation.get(0).unwrap().block_scene_update)
-            .is_some()
+            .update(&references, step);
+        for callback in callbacks.iter() {
+            callback(&mut self.shared_state);
+        }
+
+        // コールバックが要求したシーン遷移・マップ移動は、フェード中でもそのフレームで反映する
+        if !callbacks.is_empty() || !self.has_animation_blocking_scene_update() {
+            self.apply_requested_updates();
+        }
+        if !self.has_animation_blocking_scene_update() {
+            if let Some(input) = self.buffered_input.take() {
+                self.handle_input(input);
+            }
+        }
+        self.repeat_held_input(step);
+        self.dispatch_events();
+        self.report_errors();
     }
+}
 
-    fn receive_channel_message(&mut self, channel_message: &mut ChannelMessage) {
-        let mut message = channel_message.message.to_owned();
-        // TODO
-        // ネストしたJSONの扱い…
-        while let Ok(message_string) = serde_json::from_str::<String>(&message) {
-            message = message_string
+#[cfg(feature = "online")]
+impl Engine {
+    // raw_text (ChannelMessage の JSON) をサーバーから届いたメッセージとして処理する
+    // WebSocket を持たないヘッドレスのエンジンでは、これでほかのプレイヤーや折り返しを再現する
+    pub fn receive_message(&mut self, raw_text: &str) {
+        match serde_json::from_str::<ChannelMessage>(raw_text) {
+            Ok(mut channel_message) => self.receive_channel_message(&mut channel_message),
+            Err(e) => self
+                .shared_state
+                .report_error(EngineError::InvalidChannelMessage(e.to_string())),
         }
-        channel_message.message = message;
-        for scene in self.scenes.iter_mut() {
-            let consume_channel_message_func = scene.consume_channel_message_func;
-            consume_channel_message_func(scene, &mut self.shared_state, &channel_message);
+    }
+
+    // 最初に登録したシーン (タイトル) にいる間はチャンネルから抜けておく
+    fn sync_channel_membership(&mut self) {
+        let is_initial_scene =
+            Some(self.shared_state.primitives.scene_id()) == self.scenes.initial_scene_id();
+        if let Some(web_socket_wrapper) = &mut self.web_socket_wrapper {
+            if !is_initial_scene && !web_socket_wrapper.state.borrow_mut().is_joined {
+                web_socket_wrapper.join();
+            }
+            if is_initial_scene && web_socket_wrapper.state.borrow_mut().is_joined {
+                web_socket_wrapper.left();
+            }
         }
     }
 
-    pub fn animate(&mut self, step: f64) {
+    fn flush_channel_messages(&mut self) {
+        if self.web_socket_wrapper.is_some() {
+            self.communicate();
+        } else {
+            // オフラインでは送信先がないので破棄する
+            self.shared_state.to_send_channel_messages.clear();
+        }
+    }
+
+    fn communicate(&mut self) {
+        let mut web_socket_wrapper = self.web_socket_wrapper.take().unwrap();
         // 送るべきメッセージが存在していてWebSocketが切れていれば再接続
-        if !self.web_socket_wrapper.is_ready()
-            && !self.shared_state.to_send_channel_messages.is_empty()
+        if !web_socket_wrapper.is_ready() && !self.shared_state.to_send_channel_messages.is_empty()
         {
-            self.web_socket_wrapper.request_reconnect();
+            web_socket_wrapper.request_reconnect();
         }
 
+        // 解釈できなかったメッセージはエラーとして通知する
+        let errors = std::mem::take(&mut *web_socket_wrapper.errors.borrow_mut());
+        self.shared_state.errors.extend(errors);
+
         // WebSocketに届いたメッセージをアプリケーションに処理させる
-        while !(*self.web_socket_wrapper.messages.borrow_mut()).is_empty() {
-            let mut message = (*self.web_socket_wrapper.messages.borrow_mut()).remove(0);
+        while !(*web_socket_wrapper.messages.borrow_mut()).is_empty() {
+            let mut message = (*web_socket_wrapper.messages.borrow_mut()).remove(0);
             self.receive_channel_message(&mut message);
         }
 
         // 送るべきメッセージが存在していてWebSocket接続が準備できていれば全て送信
-        while self.web_socket_wrapper.is_ready()
+        while web_socket_wrapper.is_ready()
             && !self.shared_state.to_send_channel_messages.is_empty()
         {
             let message = self.shared_state.to_send_channel_messages.remove(0);
-            self.web_socket_wrapper.send_message(message);
+            web_socket_wrapper.send_message(message);
         }
+        self.web_socket_wrapper = Some(web_socket_wrapper);
+    }
 
-        let mut to_delete_indexes = vec![];
-        for (index, animation) in self
-            .shared_state
-            .interrupt_animations
-            .iter_mut()
-            .enumerate()
-        {
-            let func = animation.get(0).unwrap().animation_func;
-            let result = func(
-                animation.get_mut(0).unwrap(),
-                self.shared_state.references.clone(),
-                step,
-            );
-            if result {
-                to_delete_indexes.push(index)
-            }
+    fn receive_channel_message(&mut self, channel_message: &mut ChannelMessage) {
+        let mut message = channel_message.message.to_owned();
+        // TODO
+        // ネストしたJSONの扱い…
+        while let Ok(message_string) = serde_json::from_str::<String>(&message) {
+            message = message_string
         }
-
-        to_delete_indexes.reverse();
-        for index in to_delete_indexes.iter() {
-            let at_animations = self
-                .shared_state
-                .interrupt_animations
-                .get_mut(*index)
-                .unwrap();
-            at_animations.remove(0);
-            if at_animations.is_empty() {
-                self.shared_state.interrupt_animations.remove(*index);
-            }
+        channel_message.message = message;
+        for scene in self.scenes.iter_mut() {
+            scene.on_channel_message(&mut self.shared_state, channel_message);
         }
+    }
 
-        if self
-            .shared_state
-            .interrupt_animations
-            .iter()
-            .filter(|animation| animation.get(0).unwrap().block_scene_update)
-            .collect::<Vec<&Vec<Animation>>>()
-            .len()
-            == 0
-        {
-            if self.shared_state.primitives.scene_index
-                != self.shared_state.primitives.requested_scene_index
-            {
-                self.shared_state.primitives.scene_index =
-                    self.shared_state.primitives.requested_scene_index;
-                self.on_scene_update()
+    fn web_socket_snapshot(&self) -> Option<WebSocketSnapshot> {
+        self.web_socket_wrapper.as_ref().map(WebSocketSnapshot::new)
+    }
+}
+
+// online フィーチャーを外したビルドでは通信しない
+#[cfg(not(feature = "online"))]
+impl Engine {
+    fn sync_channel_membership(&mut self) {}
+
+    fn flush_channel_messages(&mut self) {
+        self.shared_state.to_send_channel_messages.clear();
+    }
+
+    fn web_socket_snapshot(&self) -> Option<WebSocketSnapshot> {
+        None
+    }
+}
+
+#[cfg(feature = "dev-console")]
+#[wasm_bindgen]
+impl Engine {
+    // 例: engine.exec("give 最初の鍵")  結果またはエラーの内容を返す
+    pub fn exec(&mut self, command: String) -> String {
+        self.session_log.push(SessionEvent::Exec(command.clone()));
+        self.exec_command(&command)
+    }
+
+    fn exec_command(&mut self, command: &str) -> String {
+        let result = DevCommand::parse(command)
+            .and_then(|dev_command| dev_command.apply(&mut self.shared_state));
+        let output = match result {
+            Ok(output) => {
+                // 同じマップ内のテレポートでは apply_requested_updates が再描画しないので、ここで描画し直す
+                if self.shared_state.primitives.map_index
+                    == self.shared_state.primitives.requested_map_index
+                {
+                    let scene_id = self.shared_state.primitives.scene_id().to_owned();
+                    self.with_scene(&scene_id, |scene, shared_state| {
+                        scene.on_map_update(shared_state)
+                    });
+                }
+                self.apply_requested_updates();
+                output
             }
-            if self.shared_state.primitives.map_index
-                != self.shared_state.primitives.requested_map_index
-            {
-                self.shared_state.primitives.map_index =
-                    self.shared_state.primitives.requested_map_index;
-                self.on_map_update();
+            Err(error) => {
+                let output = error.to_string();
+                self.shared_state.report_error(error);
+                output
+            }
+        };
+        self.dispatch_events();
+        self.report_errors();
+        output
+    }
+}
+
+impl Engine {
+    pub fn on_error(&mut self, error_handler: impl FnMut(&EngineError) + 'static) {
+        self.error_handlers.push(Box::new(error_handler));
+    }
+
+    // Rust 側の購読者 (JS からは subscribe を使う)
+    pub fn subscribe_events(&mut self, subscriber: impl FnMut(&GameEvent) + 'static) {
+        self.event_bus.subscribe(Box::new(subscriber));
+    }
+
+    // 記録された入力を新しいエンジンに順に流し込む
+    // セーブデータは書き換えないので、記録開始時と同じセーブデータを置いた保存先でマウントしておく (rpg::replay)
+    // セーブデータが記録と違えば同じ状態にならないので、何も再生せずにエラーを返す
+    pub fn replay(&mut self, session_log: &SessionLog) -> Result<(), EngineError> {
+        if self.session_log.initial_save != session_log.initial_save {
+            return Err(EngineError::InvalidSessionLog(
+                "the save data differs from the recorded one".to_string(),
+            ));
+        }
+        for event in session_log.events.iter() {
+            match event {
+                SessionEvent::Keydown(input) => self.consume_input(input.clone()),
+                SessionEvent::Keyup(input) => self.release_input(input.clone()),
+                SessionEvent::Animate(step) => self.advance(*step),
+                #[cfg(feature = "dev-console")]
+                SessionEvent::Exec(command) => {
+                    self.exec_command(command);
+                }
+                #[cfg(not(feature = "dev-console"))]
+                SessionEvent::Exec(_) => {}
             }
         }
+        Ok(())
     }
 }
@@ -0,0 +1,86 @@
+use crate::engine::error::EngineError;
+use crate::svg::backend::DomBackend;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// プレイヤーに見せる文言を id で引く
+// JSON は {"ja": {"id": "文言", ...}, "en": {...}} の形で、文言中の {name} は引数で置き換える
+#[derive(Clone)]
+pub struct Catalog {
+    locale: String,
+    // 今のロケールに無い id はこのロケールで引く
+    fallback_locale: String,
+    messages: HashMap<String, HashMap<String, String>>,
+}
+
+impl Catalog {
+    pub fn from_json(json: &str, locale: &str) -> Result<Catalog, EngineError> {
+        let messages = Catalog::parse(json)?;
+        if !messages.contains_key(locale) {
+            return Err(EngineError::UnknownLocale(locale.to_string()));
+        }
+        Ok(Catalog {
+            locale: locale.to_string(),
+            fallback_locale: locale.to_string(),
+            messages,
+        })
+    }
+
+    fn parse(json: &str) -> Result<HashMap<String, HashMap<String, String>>, EngineError> {
+        serde_json::from_str(json).map_err(|e| EngineError::InvalidCatalog(e.to_string()))
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub fn set_locale(&mut self, locale: &str) -> Result<(), EngineError> {
+        if !self.messages.contains_key(locale) {
+            return Err(EngineError::UnknownLocale(locale.to_string()));
+        }
+        self.locale = locale.to_string();
+        Ok(())
+    }
+
+    // 同じ id は上書きし、新しいロケールは追加する
+    pub fn merge_json(&mut self, json: &str) -> Result<(), EngineError> {
+        for (locale, messages) in Catalog::parse(json)? {
+            self.messages.entry(locale).or_default().extend(messages);
+        }
+        Ok(())
+    }
+
+    // どのロケールにも無い id はそのまま返す (アイテム名など翻訳済みの文字列も渡せる)
+    pub fn text(&self, id: &str) -> String {
+        [&self.locale, &self.fallback_locale]
+            .iter()
+            .find_map(|locale| self.messages.get(*locale)?.get(id))
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    pub fn format(&self, id: &str, args: &[(&str, &str)]) -> String {
+        let mut text = self.text(id);
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+
+    // 複数行のメッセージは改行で区切って書く
+    pub fn lines(&self, id: &str) -> Vec<String> {
+        self.text(id)
+            .split('\n')
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    // マークアップに直接書かれた文言 (class="localized" data-message-id="...") を書き換える
+    pub fn apply(&self, document: &Rc<dyn DomBackend>) {
+        for element in document.query_selector_all(".localized") {
+            if let Some(id) = element.get_attribute("data-message-id") {
+                element.set_inner_html(&self.text(&id));
+            }
+        }
+    }
+}
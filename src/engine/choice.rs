@@ -82,8 +82,8 @@ impl Choice {
             own_token: Confirm,
             branch_description: None,
             branch: Some(vec![
-                Choice::no_choice_from_with_label(Decide, "はい".to_string()),
-                Choice::no_choice_from_with_label(Undo, "いいえ".to_string()),
+                Choice::no_choice_from_with_label(Decide, Yes.get_choice_string()),
+                Choice::no_choice_from_with_label(Undo, No.get_choice_string()),
             ]),
         }
     }
@@ -0,0 +1,62 @@
+// ホストから渡される step から一時停止していた時間を差し引いて、ゲーム内の step を求める
+// タブが裏に回っている間に進んだ時間でアニメーションが飛ばないようにする
+#[derive(Clone)]
+pub struct Clock {
+    paused: bool,
+    // 次の animate までの経過時間を一時停止していた時間として扱う
+    frozen: bool,
+    last_host_step: f64,
+    paused_duration: f64,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock {
+            paused: false,
+            frozen: false,
+            last_host_step: 0.0,
+            paused_duration: 0.0,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // 既に一時停止している場合は false
+    pub fn pause(&mut self) -> bool {
+        if self.paused {
+            return false;
+        }
+        self.paused = true;
+        self.frozen = true;
+        true
+    }
+
+    pub fn resume(&mut self) -> bool {
+        if !self.paused {
+            return false;
+        }
+        self.paused = false;
+        true
+    }
+
+    // 一時停止中は None
+    pub fn tick(&mut self, host_step: f64) -> Option<f64> {
+        if self.frozen {
+            self.paused_duration += host_step - self.last_host_step;
+        }
+        self.last_host_step = host_step;
+        if self.paused {
+            return None;
+        }
+        self.frozen = false;
+        Some(host_step - self.paused_duration)
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new()
+    }
+}
@@ -0,0 +1,75 @@
+use serde::Serialize;
+use std::fmt;
+
+// 壊れたセーブデータや不正なメッセージでは wasm ごと落とさず、ホストに通知して処理を続ける
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", content = "detail")]
+pub enum EngineError {
+    UnknownItem(String),
+    UnknownInput(String),
+    UnknownScene(String),
+    InvalidSceneRegistration(String),
+    InvalidSceneTransition(String),
+    CorruptedSave(String),
+    InvalidSessionLog(String),
+    InvalidKeyBindings(String),
+    InvalidChannelMessage(String),
+    InvalidCommand(String),
+    UnknownSnapshot(usize),
+    RewindUnavailable(usize),
+    UnknownLocale(String),
+    InvalidCatalog(String),
+    UnknownEvent(String),
+    InvalidEventScript(String),
+    UnsupportedStateType(String),
+    InvalidMarkup(String),
+    MissingElement(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::UnknownItem(name) => write!(f, "unknown item `{}`", name),
+            EngineError::UnknownInput(name) => write!(f, "unknown input `{}`", name),
+            EngineError::UnknownScene(scene_id) => write!(f, "unknown scene `{}`", scene_id),
+            EngineError::InvalidSceneRegistration(reason) => {
+                write!(f, "invalid scene registration: {}", reason)
+            }
+            EngineError::InvalidSceneTransition(reason) => {
+                write!(f, "invalid scene transition: {}", reason)
+            }
+            EngineError::CorruptedSave(reason) => write!(f, "corrupted save data: {}", reason),
+            EngineError::InvalidSessionLog(reason) => write!(f, "invalid session log: {}", reason),
+            EngineError::InvalidKeyBindings(reason) => {
+                write!(f, "invalid key bindings: {}", reason)
+            }
+            EngineError::InvalidChannelMessage(reason) => {
+                write!(f, "invalid channel message: {}", reason)
+            }
+            EngineError::InvalidCommand(command) => write!(f, "invalid command: {}", command),
+            EngineError::UnknownSnapshot(snapshot_id) => {
+                write!(f, "unknown snapshot `{}`", snapshot_id)
+            }
+            EngineError::RewindUnavailable(snapshot_id) => write!(
+                f,
+                "cannot rewind to snapshot `{}`: the engine has no remount",
+                snapshot_id
+            ),
+            EngineError::UnknownLocale(locale) => write!(f, "unknown locale `{}`", locale),
+            EngineError::InvalidCatalog(reason) => write!(f, "invalid catalog: {}", reason),
+            EngineError::UnknownEvent(event_id) => write!(f, "unknown event `{}`", event_id),
+            EngineError::InvalidEventScript(reason) => {
+                write!(f, "invalid event script: {}", reason)
+            }
+            EngineError::UnsupportedStateType(reason) => {
+                write!(f, "unsupported state type: {}", reason)
+            }
+            EngineError::InvalidMarkup(reason) => write!(f, "invalid markup: {}", reason),
+            EngineError::MissingElement(id) => write!(f, "missing element `#{}`", id),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+pub type ErrorHandler = Box<dyn FnMut(&EngineError)>;
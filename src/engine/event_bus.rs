@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+// シーンやエンジンが発行し、購読者に配信されるイベント
+// JS には {"type": "ItemAcquired", ...} の形で渡す
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum GameEvent {
+    SceneChanged { from: String, to: String },
+    MapChanged { from: usize, to: usize },
+    ItemAcquired { item_name: String, map_index: usize },
+    GateOpened { key_name: String, map_index: usize },
+    BattleStarted { map_index: usize },
+    PlayerJoined { user_name: String },
+    PlayerLeft { user_name: String },
+}
+
+pub type Subscriber = Box<dyn FnMut(&GameEvent)>;
+
+pub struct EventBus {
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus {
+            subscribers: vec![],
+        }
+    }
+
+    pub fn subscribe(&mut self, subscriber: Subscriber) {
+        self.subscribers.push(subscriber);
+    }
+
+    // 購読者は登録順に呼ばれる
+    pub fn dispatch(&mut self, events: Vec<GameEvent>) {
+        for event in events.iter() {
+            for subscriber in self.subscribers.iter_mut() {
+                subscriber(event);
+            }
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
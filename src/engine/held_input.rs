@@ -0,0 +1,159 @@
+use crate::engine::input::Input;
+
+// 押しっぱなしの矢印キーを一定間隔で入力し直すための状態
+// Context も押されている間を覚えておく (リピートはしない)
+// 時刻はすべて animate に渡される step (ミリ秒)
+pub struct HeldInputs {
+    pub repeat_delay: f64,
+    pub repeat_rate: f64,
+    // 押された順 (リピートされるのは最後に押された矢印キーだけ)
+    inputs: Vec<Input>,
+    next_repeat_at: Option<f64>,
+}
+
+impl HeldInputs {
+    pub fn new() -> HeldInputs {
+        HeldInputs {
+            repeat_delay: 250.0,
+            repeat_rate: 120.0,
+            inputs: vec![],
+            next_repeat_at: None,
+        }
+    }
+
+    pub fn is_repeatable(input: &Input) -> bool {
+        matches!(
+            input,
+            Input::ArrowUp | Input::ArrowDown | Input::ArrowRight | Input::ArrowLeft
+        )
+    }
+
+    // 押している間だけ効くキー (Context を押しながら歩くと走る)
+    pub fn is_holdable(input: &Input) -> bool {
+        HeldInputs::is_repeatable(input) || *input == Input::Context
+    }
+
+    pub fn is_held(&self, input: &Input) -> bool {
+        self.inputs.contains(input)
+    }
+
+    pub fn held(&self) -> Vec<Input> {
+        self.inputs.to_vec()
+    }
+
+    // 既に押されている場合 (OS のキーリピートなど) は false
+    pub fn press(&mut self, input: Input, now: f64) -> bool {
+        if self.is_held(&input) {
+            return false;
+        }
+        if HeldInputs::is_repeatable(&input) {
+            self.next_repeat_at = Some(now + self.repeat_delay);
+        }
+        self.inputs.push(input);
+        true
+    }
+
+    // 他に押されたままのキーがあれば、そちらが続けてリピートされる
+    pub fn release(&mut self, input: &Input, now: f64) {
+        self.inputs.retain(|held_input| held_input != input);
+        if !HeldInputs::is_repeatable(input) {
+            return;
+        }
+        self.next_repeat_at = self.repeating().map(|_| now + self.repeat_rate);
+    }
+
+    // 離した入力を押された順に返す
+    pub fn release_all(&mut self) -> Vec<Input> {
+        self.next_repeat_at = None;
+        std::mem::take(&mut self.inputs)
+    }
+
+    pub fn due(&self, now: f64) -> Option<Input> {
+        match self.next_repeat_at {
+            Some(next_repeat_at) if now >= next_repeat_at => self.repeating(),
+            _ => None,
+        }
+    }
+
+    pub fn repeated(&mut self, now: f64) {
+        self.next_repeat_at = Some(now + self.repeat_rate);
+    }
+
+    fn repeating(&self) -> Option<Input> {
+        self.inputs
+            .iter()
+            .rev()
+            .find(|input| HeldInputs::is_repeatable(input))
+            .cloned()
+    }
+}
+
+impl Default for HeldInputs {
+    fn default() -> Self {
+        HeldInputs::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_after_delay_then_at_rate() {
+        let mut held_inputs = HeldInputs::new();
+        assert!(held_inputs.press(Input::ArrowLeft, 1000.0));
+        assert_eq!(held_inputs.due(1249.0), None);
+        assert_eq!(held_inputs.due(1250.0), Some(Input::ArrowLeft));
+        held_inputs.repeated(1250.0);
+        assert_eq!(held_inputs.due(1369.0), None);
+        assert_eq!(held_inputs.due(1370.0), Some(Input::ArrowLeft));
+    }
+
+    #[test]
+    fn pressing_held_input_again_is_ignored() {
+        let mut held_inputs = HeldInputs::new();
+        assert!(held_inputs.press(Input::ArrowUp, 0.0));
+        assert!(!held_inputs.press(Input::ArrowUp, 100.0));
+        // 二度目の押下で遅延をやり直さない
+        assert_eq!(held_inputs.due(250.0), Some(Input::ArrowUp));
+    }
+
+    #[test]
+    fn last_pressed_arrow_repeats_and_falls_back_on_release() {
+        let mut held_inputs = HeldInputs::new();
+        held_inputs.press(Input::ArrowUp, 0.0);
+        held_inputs.press(Input::ArrowRight, 100.0);
+        assert_eq!(held_inputs.due(349.0), None);
+        assert_eq!(held_inputs.due(350.0), Some(Input::ArrowRight));
+        held_inputs.release(&Input::ArrowRight, 400.0);
+        assert_eq!(held_inputs.due(519.0), None);
+        assert_eq!(held_inputs.due(520.0), Some(Input::ArrowUp));
+        held_inputs.release(&Input::ArrowUp, 600.0);
+        assert_eq!(held_inputs.due(10000.0), None);
+    }
+
+    #[test]
+    fn context_is_held_without_repeating() {
+        let mut held_inputs = HeldInputs::new();
+        held_inputs.press(Input::ArrowDown, 0.0);
+        held_inputs.press(Input::Context, 100.0);
+        assert!(held_inputs.is_held(&Input::Context));
+        // Context を押しても矢印のリピートの時刻は変わらない
+        assert_eq!(held_inputs.due(250.0), Some(Input::ArrowDown));
+        held_inputs.release(&Input::Context, 260.0);
+        assert_eq!(held_inputs.due(250.0), Some(Input::ArrowDown));
+    }
+
+    #[test]
+    fn release_all_stops_repeating() {
+        let mut held_inputs = HeldInputs::new();
+        held_inputs.press(Input::ArrowLeft, 0.0);
+        held_inputs.press(Input::Context, 10.0);
+        assert_eq!(
+            held_inputs.release_all(),
+            vec![Input::ArrowLeft, Input::Context]
+        );
+        assert!(held_inputs.held().is_empty());
+        assert_eq!(held_inputs.due(10000.0), None);
+    }
+}
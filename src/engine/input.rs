@@ -1,6 +1,7 @@
+use crate::engine::error::EngineError;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Input {
     Enter,
     Cancel,
@@ -13,15 +14,17 @@ pub enum Input {
 }
 
 impl Input {
-    pub fn from(key: String) -> Input {
-        match key.as_str() {
-            "a" => Input::Enter,
-            "z" => Input::Cancel,
+    // キーではなくアクション名から変換する (キーからの変換は KeyBindings で行う)
+    pub fn from_name(name: &str) -> Result<Input, EngineError> {
+        Ok(match name {
+            "Enter" => Input::Enter,
+            "Cancel" => Input::Cancel,
+            "Context" => Input::Context,
             "ArrowRight" => Input::ArrowRight,
             "ArrowLeft" => Input::ArrowLeft,
             "ArrowUp" => Input::ArrowUp,
             "ArrowDown" => Input::ArrowDown,
-            _ => Input::None,
-        }
+            _ => return Err(EngineError::UnknownInput(name.to_string())),
+        })
     }
 }
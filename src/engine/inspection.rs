@@ -0,0 +1,106 @@
+use crate::engine::state::State;
+use crate::features::animation::Animation;
+#[cfg(feature = "online")]
+use crate::features::websocket::WebSocketWrapper;
+use serde::Serialize;
+
+// ゲーム側の状態を書き出す (マウント時にゲームが登録する、エンジンはゲームの型を知らない)
+pub type InspectGame = Box<dyn Fn(&State) -> Option<serde_json::Value>>;
+
+// ページ側のデバッグパネル向けに、エンジンの状態を読み取り専用で書き出す
+#[derive(Serialize)]
+pub struct StateSnapshot {
+    pub scene_id: String,
+    pub scene_stack: Vec<String>,
+    pub map_index: usize,
+    pub requested_map_index: usize,
+    pub user_name: String,
+    pub seed: u64,
+    // seed から引いた乱数の数
+    pub rng_word_pos: u128,
+    pub has_block_message: bool,
+    pub interrupt_animations: Vec<Vec<AnimationSnapshot>>,
+    pub to_send_channel_messages: Vec<String>,
+    // InspectGame が書き出した状態
+    pub game: Option<serde_json::Value>,
+    pub web_socket: Option<WebSocketSnapshot>,
+}
+
+#[derive(Serialize)]
+pub struct AnimationSnapshot {
+    pub block_scene_update: bool,
+    pub start_step: f64,
+    pub span: i32,
+    pub messages: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct WebSocketSnapshot {
+    pub is_opened: bool,
+    pub is_closed: bool,
+    pub is_joined: bool,
+    pub has_connection_request: bool,
+    pub received_messages: usize,
+}
+
+impl StateSnapshot {
+    pub fn new(
+        shared_state: &State,
+        web_socket: Option<WebSocketSnapshot>,
+        inspect_game: Option<&InspectGame>,
+    ) -> StateSnapshot {
+        StateSnapshot {
+            scene_id: shared_state.primitives.scene_id().to_string(),
+            scene_stack: shared_state.primitives.scene_stack.to_vec(),
+            map_index: shared_state.primitives.map_index,
+            requested_map_index: shared_state.primitives.requested_map_index,
+            user_name: shared_state.user_name.to_owned(),
+            seed: shared_state.seed,
+            rng_word_pos: shared_state.rng.get_word_pos(),
+            has_block_message: shared_state.references.borrow().has_block_message,
+            interrupt_animations: shared_state
+                .interrupt_animations
+                .tracks()
+                .map(|track| {
+                    track
+                        .animations()
+                        .into_iter()
+                        .map(AnimationSnapshot::new)
+                        .collect()
+                })
+                .collect(),
+            to_send_channel_messages: shared_state.to_send_channel_messages.to_vec(),
+            game: inspect_game.and_then(|inspect_game| inspect_game(shared_state)),
+            web_socket,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+#[cfg(feature = "online")]
+impl WebSocketSnapshot {
+    pub fn new(web_socket_wrapper: &WebSocketWrapper) -> WebSocketSnapshot {
+        let state = web_socket_wrapper.state.borrow();
+        WebSocketSnapshot {
+            is_opened: state.is_opened,
+            is_closed: state.is_closed,
+            is_joined: state.is_joined,
+            has_connection_request: state.has_connection_request,
+            received_messages: web_socket_wrapper.messages.borrow().len(),
+        }
+    }
+}
+
+impl AnimationSnapshot {
+    fn new(animation: &Animation) -> AnimationSnapshot {
+        AnimationSnapshot {
+            block_scene_update: animation.block_scene_update,
+            start_step: animation.start_step,
+            span: animation.span as i32,
+            messages: animation.messages.to_vec(),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::engine::error::EngineError;
+use crate::engine::input::Input;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyBinding {
+    pub input: Input,
+    pub keys: Vec<String>,
+}
+
+// KeyboardEvent.key からアクションへの対応表 (1つのアクションに複数のキーを割り当てられる)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyBindings {
+    bindings: Vec<KeyBinding>,
+}
+
+impl KeyBindings {
+    pub fn new() -> KeyBindings {
+        let binding = |input: Input, keys: &[&str]| KeyBinding {
+            input,
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+        };
+        KeyBindings {
+            bindings: vec![
+                binding(Input::Enter, &["a"]),
+                binding(Input::Cancel, &["z"]),
+                binding(Input::Context, &["x"]),
+                binding(Input::ArrowRight, &["ArrowRight"]),
+                binding(Input::ArrowLeft, &["ArrowLeft"]),
+                binding(Input::ArrowUp, &["ArrowUp"]),
+                binding(Input::ArrowDown, &["ArrowDown"]),
+            ],
+        }
+    }
+
+    pub fn resolve(&self, key: &str) -> Input {
+        self.bindings
+            .iter()
+            .find(|binding| binding.keys.iter().any(|bound_key| bound_key == key))
+            .map(|binding| binding.input.clone())
+            .unwrap_or(Input::None)
+    }
+
+    pub fn keys_for(&self, input: &Input) -> Vec<String> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.input == *input)
+            .map(|binding| binding.keys.to_vec())
+            .unwrap_or_default()
+    }
+
+    // 既に他のアクションに割り当てられているキーはそちらから外す
+    pub fn bind(&mut self, input: Input, key: &str) {
+        self.unbind(key);
+        match self
+            .bindings
+            .iter_mut()
+            .find(|binding| binding.input == input)
+        {
+            Some(binding) => binding.keys.push(key.to_string()),
+            None => self.bindings.push(KeyBinding {
+                input,
+                keys: vec![key.to_string()],
+            }),
+        }
+    }
+
+    pub fn unbind(&mut self, key: &str) {
+        for binding in self.bindings.iter_mut() {
+            binding.keys.retain(|bound_key| bound_key != key);
+        }
+    }
+
+    pub fn set_keys(&mut self, input: Input, keys: Vec<String>) {
+        for key in keys.iter() {
+            self.unbind(key);
+        }
+        match self
+            .bindings
+            .iter_mut()
+            .find(|binding| binding.input == input)
+        {
+            Some(binding) => binding.keys = keys,
+            None => self.bindings.push(KeyBinding { input, keys }),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<KeyBindings, EngineError> {
+        serde_json::from_str(json).map_err(|e| EngineError::InvalidKeyBindings(e.to_string()))
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::new()
+    }
+}
@@ -1,37 +1,131 @@
-use crate::engine::application_types::SceneType;
+use crate::engine::error::EngineError;
 use crate::engine::input::Input;
 use crate::engine::state::State;
+#[cfg(feature = "online")]
 use crate::features::websocket::ChannelMessage;
 use crate::svg::element_wrapper::ElementWrapper;
+use std::rc::Rc;
 
-pub struct Scene {
-    pub own_element: ElementWrapper,
-    pub scene_type: SceneType,
-    pub is_partial_scene: bool,
-    pub consume_func: fn(scene: &mut Scene, shared_state: &mut State, input: Input),
-    pub init_func: fn(scene: &mut Scene, shared_state: &mut State),
-    pub update_map_func: fn(scene: &mut Scene, shared_state: &mut State),
-    pub consume_channel_message_func:
-        fn(scene: &mut Scene, shared_state: &mut State, message: &ChannelMessage),
+// ゲーム側のクレートも実装して SceneRegistry に登録できる
+pub trait Scene {
+    fn own_element(&self) -> &ElementWrapper;
+
+    // このシーンから遷移しうるシーンの識別子 (登録時に存在を検証し、ここに無い遷移の要求はエラーにする)
+    fn next_scene_ids(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn init(&mut self, shared_state: &mut State);
+
+    fn consume_input(&mut self, shared_state: &mut State, input: Input);
+
+    fn on_map_update(&mut self, _shared_state: &mut State) {}
+
+    #[cfg(feature = "online")]
+    fn on_channel_message(&mut self, _shared_state: &mut State, _message: &ChannelMessage) {}
+
+    // 上に別のシーンが積まれた時・取り除かれた時に呼ばれる
+    fn pause(&mut self, _shared_state: &mut State) {}
+
+    fn resume(&mut self, _shared_state: &mut State) {}
+
+    fn hide(&self) {
+        self.own_element().hide();
+    }
+
+    fn show(&self) {
+        self.own_element().show();
+    }
+}
+
+// 組み込みのシーンに加えてゲーム側のシーンを登録する
+pub type RegisterScenes = Rc<dyn Fn(&mut SceneRegistry, &mut State) -> Result<(), EngineError>>;
+
+pub struct SceneRegistry {
+    scene_ids: Vec<String>,
+    scenes: Vec<Box<dyn Scene>>,
 }
 
-impl Scene {
-    pub fn hide(&self) {
-        self.own_element.hide();
+impl SceneRegistry {
+    pub fn new() -> SceneRegistry {
+        SceneRegistry {
+            scene_ids: vec![],
+            scenes: vec![],
+        }
     }
 
-    pub fn show(&self) {
-        self.own_element.show();
+    // 空の識別子や登録済みの識別子では登録しない
+    pub fn register<S: Scene + 'static>(
+        &mut self,
+        scene_id: &str,
+        scene: S,
+    ) -> Result<&mut SceneRegistry, EngineError> {
+        if scene_id.is_empty() {
+            return Err(EngineError::InvalidSceneRegistration(
+                "scene id must not be empty".to_string(),
+            ));
+        }
+        if self.contains(scene_id) {
+            return Err(EngineError::InvalidSceneRegistration(format!(
+                "scene `{}` is already registered",
+                scene_id
+            )));
+        }
+        self.scene_ids.push(scene_id.to_string());
+        self.scenes.push(Box::new(scene));
+        Ok(self)
     }
 
-    pub fn create_update_map_func_empty() -> fn(&mut Scene, &mut State) {
-        fn update_map_func(_: &mut Scene, _: &mut State) {}
-        update_map_func
+    // すべてのシーンを登録した後に呼ぶ
+    pub fn validate(&self) -> Result<(), EngineError> {
+        for (scene_id, scene) in self.scene_ids.iter().zip(self.scenes.iter()) {
+            for next_scene_id in scene.next_scene_ids() {
+                if !self.contains(next_scene_id) {
+                    return Err(EngineError::InvalidSceneRegistration(format!(
+                        "scene `{}` requests unregistered scene `{}`",
+                        scene_id, next_scene_id
+                    )));
+                }
+            }
+        }
+        Ok(())
     }
 
-    pub fn create_consume_channel_message_func_empty() -> fn(&mut Scene, &mut State, &ChannelMessage)
-    {
-        fn consume_channel_message_func(_: &mut Scene, _: &mut State, _: &ChannelMessage) {}
-        consume_channel_message_func
+    pub fn contains(&self, scene_id: &str) -> bool {
+        self.scene_ids.iter().any(|id| id == scene_id)
+    }
+
+    pub fn initial_scene_id(&self) -> Option<&str> {
+        self.scene_ids.first().map(|scene_id| scene_id.as_str())
+    }
+
+    pub fn get(&self, scene_id: &str) -> Result<&dyn Scene, EngineError> {
+        Ok(self.scenes[self.index(scene_id)?].as_ref())
+    }
+
+    pub fn get_mut(&mut self, scene_id: &str) -> Result<&mut dyn Scene, EngineError> {
+        let index = self.index(scene_id)?;
+        Ok(self.scenes[index].as_mut())
+    }
+
+    fn index(&self, scene_id: &str) -> Result<usize, EngineError> {
+        self.scene_ids
+            .iter()
+            .position(|id| id == scene_id)
+            .ok_or_else(|| EngineError::UnknownScene(scene_id.to_string()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Scene> {
+        self.scenes.iter().map(|scene| scene.as_ref())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut (dyn Scene + 'static)> {
+        self.scenes.iter_mut().map(|scene| scene.as_mut())
+    }
+}
+
+impl Default for SceneRegistry {
+    fn default() -> Self {
+        SceneRegistry::new()
     }
 }
@@ -0,0 +1,53 @@
+use crate::engine::error::EngineError;
+use crate::engine::input::Input;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum SessionEvent {
+    Keydown(Input),
+    Keyup(Input),
+    Animate(f64),
+    // dev-console ビルドの engine.exec() で記録される (ほかのビルドでは再生時に読み飛ばす)
+    Exec(String),
+}
+
+// 同じシード・同じセーブデータから同じ入力を与えれば同じ状態が再現される
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionLog {
+    pub seed: u64,
+    pub initial_save: Option<String>,
+    pub events: Vec<SessionEvent>,
+}
+
+impl SessionLog {
+    pub fn new(seed: u64, initial_save: Option<String>) -> SessionLog {
+        SessionLog {
+            seed,
+            initial_save,
+            events: vec![],
+        }
+    }
+
+    pub fn push(&mut self, event: SessionEvent) {
+        self.events.push(event);
+    }
+
+    // 直前の Animate の step を書き換える (何も起きなかったフレームを記録し続けない)
+    pub fn replace_last_animate(&mut self, step: f64) -> bool {
+        match self.events.last_mut() {
+            Some(SessionEvent::Animate(last_step)) => {
+                *last_step = step;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<SessionLog, EngineError> {
+        serde_json::from_str(json).map_err(|e| EngineError::InvalidSessionLog(e.to_string()))
+    }
+}
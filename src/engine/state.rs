@@ -1,12 +1,30 @@
 use crate::engine::application_types::StateType;
-use crate::features::animation::Animation;
+use crate::engine::catalog::Catalog;
+use crate::engine::error::EngineError;
+use crate::engine::event_bus::GameEvent;
+use crate::engine::input::Input;
+use crate::engine::key_bindings::KeyBindings;
+use crate::features::timeline::Timeline;
 use crate::svg::SharedElements;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+#[derive(Clone)]
+pub enum SceneTransition {
+    // スタックを空にしてから積む
+    Switch(String),
+    // 今のシーンの上に重ねる (メニューなど)
+    Push(String),
+    // 一番上のシーンを取り除き、下のシーンに戻る
+    Pop,
+}
+
 pub struct Primitives {
-    pub scene_index: usize,
-    pub requested_scene_index: usize,
+    // 末尾が現在のシーン
+    pub scene_stack: Vec<String>,
+    pub requested_scene_transition: Option<SceneTransition>,
     pub map_index: usize,
     pub requested_map_index: usize,
 }
@@ -14,14 +32,75 @@ pub struct Primitives {
 pub struct References {
     pub has_block_message: bool,
     pub has_continuous_message: bool,
+    // メッセージを 1 文字ずつ表示している途中
+    pub revealing_message: bool,
+    // 表示途中のメッセージを一度に出す (決定キーで立てる)
+    pub complete_message_requested: bool,
+    // 1 文字あたりのミリ秒
+    pub text_speed: f64,
 }
 
 pub struct State {
     pub user_name: String,
     pub to_send_channel_messages: Vec<String>,
+    // エンジンが入力・フレームの処理後にまとめて購読者へ配信する
+    pub emitted_events: Vec<GameEvent>,
+    // 同じく処理後にまとめてエラーハンドラへ通知する
+    pub errors: Vec<EngineError>,
     pub state_type: StateType,
     pub elements: SharedElements,
-    pub interrupt_animations: Vec<Vec<Animation>>,
+    // push したアニメーションの並びは並行して動く (Timeline::play で組み合わせも渡せる)
+    pub interrupt_animations: Timeline,
     pub primitives: Primitives,
     pub references: Rc<RefCell<References>>,
+    // 乱数はすべてここから引く (シードが同じなら同じ入力で同じ結果になる)
+    pub rng: ChaCha12Rng,
+    // rng を作った時のシード (ロードするとセーブデータのシードに変わる)
+    pub seed: u64,
+    pub key_bindings: KeyBindings,
+    // 押されたままの矢印キーと Context (シーンが組み合わせて使う)
+    pub held_inputs: Vec<Input>,
+    // 描画側 (SvgRenderer) からも引けるよう共有する
+    pub catalog: Rc<RefCell<Catalog>>,
+}
+
+impl Primitives {
+    pub fn scene_id(&self) -> &str {
+        self.scene_stack.last().unwrap()
+    }
+    pub fn request_scene(&mut self, scene_id: &str) {
+        self.requested_scene_transition = Some(SceneTransition::Switch(scene_id.to_string()));
+    }
+    pub fn push_scene(&mut self, scene_id: &str) {
+        self.requested_scene_transition = Some(SceneTransition::Push(scene_id.to_string()));
+    }
+    pub fn pop_scene(&mut self) {
+        self.requested_scene_transition = Some(SceneTransition::Pop);
+    }
+}
+
+impl State {
+    pub fn emit(&mut self, event: GameEvent) {
+        self.emitted_events.push(event);
+    }
+
+    pub fn report_error(&mut self, error: EngineError) {
+        self.errors.push(error);
+    }
+
+    pub fn create_rng(seed: Option<u64>) -> (u64, ChaCha12Rng) {
+        let seed = seed.unwrap_or_else(|| {
+            let mut bytes = [0_u8; 8];
+            getrandom::getrandom(&mut bytes).unwrap();
+            u64::from_le_bytes(bytes)
+        });
+        (seed, ChaCha12Rng::seed_from_u64(seed))
+    }
+
+    // seed から word_pos 個引いたところまで進めた乱数に戻す (セーブした時点の続きから引ける)
+    pub fn restore_rng(&mut self, seed: u64, word_pos: u128) {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self.rng.set_word_pos(word_pos);
+        self.seed = seed;
+    }
 }
@@ -0,0 +1,106 @@
+use crate::engine::error::EngineError;
+use crate::engine::input::Input;
+use crate::engine::inspection::StateSnapshot;
+use crate::engine::scene::SceneRegistry;
+use crate::engine::state::State;
+use crate::engine::Engine;
+use crate::svg::backend::DomBackend;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+// 古いものから捨てる
+pub const SNAPSHOT_CAPACITY: usize = 64;
+
+// 同じシード・同じシーン構成のエンジンを、渡した描画先・保存先でオフラインで作り直す
+pub type Remount = Rc<dyn Fn(Rc<dyn DomBackend>) -> Engine>;
+
+// keydown を処理する直前の状態
+#[derive(Serialize)]
+pub struct Snapshot {
+    pub id: usize,
+    // この入力より前に記録されたセッションログのイベント数
+    pub event_index: usize,
+    pub step: f64,
+    pub input: Input,
+    pub state: StateSnapshot,
+}
+
+pub struct TimeTravel {
+    pub(crate) remount: Option<Remount>,
+    // 最初にマウントした描画先 (巻き戻すたびに包み直さないよう、作り直したエンジンにも引き継ぐ)
+    pub(crate) document: Rc<dyn DomBackend>,
+    // エンジン作成直後の描画先の要素 (id とマークアップ)
+    pub(crate) initial_markups: Vec<(String, String)>,
+    snapshots: VecDeque<Snapshot>,
+    next_id: usize,
+}
+
+impl TimeTravel {
+    pub fn new(shared_state: &State, scenes: &SceneRegistry) -> TimeTravel {
+        let document = &shared_state.elements.document;
+        // フェード用の要素はどのシーンにも属さないので個別に加える
+        let mut root_ids = vec![];
+        let roots = scenes
+            .iter()
+            .map(|scene| &scene.own_element().element)
+            .chain([&shared_state.elements.message.element]);
+        for root in roots {
+            if let Some(id) = root.get_attribute("id") {
+                root_ids.push(id);
+            }
+        }
+        root_ids.push("fader".to_string());
+        let mut initial_markups = vec![];
+        for id in root_ids {
+            // 複数のシーンが同じ要素を使うこともある
+            if initial_markups.iter().any(|(root_id, _)| *root_id == id) {
+                continue;
+            }
+            if let Some(element) = document.get_element_by_id(&id) {
+                initial_markups.push((id, element.outer_html()));
+            }
+        }
+        TimeTravel {
+            remount: None,
+            document: document.clone(),
+            initial_markups,
+            snapshots: VecDeque::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn record(&mut self, event_index: usize, step: f64, input: Input, state: StateSnapshot) {
+        if self.snapshots.len() == SNAPSHOT_CAPACITY {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot {
+            id: self.next_id,
+            event_index,
+            step,
+            input,
+            state,
+        });
+        self.next_id += 1;
+    }
+
+    pub fn get(&self, snapshot_id: usize) -> Option<&Snapshot> {
+        self.snapshots
+            .iter()
+            .find(|snapshot| snapshot.id == snapshot_id)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.snapshots).unwrap()
+    }
+
+    // 描画先をエンジン作成直後の状態に戻す
+    pub fn restore_markups(&self, document: &Rc<dyn DomBackend>) -> Result<(), EngineError> {
+        for (id, markup) in self.initial_markups.iter() {
+            if let Some(element) = document.get_element_by_id(id) {
+                element.replace_with_markup(markup)?;
+            }
+        }
+        Ok(())
+    }
+}
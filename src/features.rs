@@ -1,3 +1,9 @@
 pub mod animation;
+pub mod camera;
+#[cfg(feature = "emote")]
 pub mod emote;
+pub mod timeline;
+pub mod tween;
+pub mod typewriter;
+#[cfg(feature = "online")]
 pub mod websocket;
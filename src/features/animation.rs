@@ -1,11 +1,16 @@
-use crate::engine::state::References;
+use crate::engine::state::{References, State};
+use crate::features::camera::Camera;
+use crate::features::tween::{Easing, Tween};
+use crate::features::typewriter::Typewriter;
+use crate::svg::backend::{DomBackend, Element};
+use crate::svg::{Position, SharedElements};
 use std::cell::RefCell;
+use std::f64::consts::PI;
 use std::rc::Rc;
-use wasm_bindgen::JsCast;
-use wasm_bindgen_test::console_log;
-use web_sys::Element;
 
+#[derive(Clone)]
 pub struct Animation {
+    pub document: Rc<dyn DomBackend>,
     pub args_i32: Vec<i32>,
     pub block_scene_update: bool,
     pub animation_func: fn(&mut Animation, Rc<RefCell<References>>, step: f64) -> bool,
@@ -13,6 +18,28 @@ pub struct Animation {
     pub start_step: f64,
     pub span: AnimationSpan,
     pub messages: Vec<String>,
+    // create_tween で作ったアニメーションが経過時間に合わせて書き込む
+    pub tweens: Vec<Tween>,
+    // この経過時間を過ぎたらシーン遷移を止めない (None なら終わるまで)
+    pub release_block_at: Option<f64>,
+    // 指定した時点に達したら State を渡して呼ぶ
+    pub cues: Vec<(Cue, AnimationCallback)>,
+    // メッセージのアニメーションが表示中のページ
+    pub typewriter: Option<Typewriter>,
+    // シーン遷移を止めている間の入力を捨てずに、終わってから処理させる
+    pub buffer_input: bool,
+}
+
+// アニメーションの途中・終了時に呼ぶ処理 (シーン遷移やマップの切り替えなど)
+pub type AnimationCallback = Rc<dyn Fn(&mut State)>;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Cue {
+    // 開始からの経過時間 (ミリ秒)
+    At(f64),
+    // 長さの半分 (フェードなら真っ暗になった時点)
+    Midpoint,
+    Complete,
 }
 
 impl Animation {
@@ -25,252 +52,324 @@ impl Animation {
         step - self.start_step
     }
 
-    pub fn always_blink() -> Animation {
-        let node_list = web_sys::window()
-            .unwrap()
-            .document()
-            .unwrap()
-            .query_selector_all(".always-blink")
-            .unwrap();
-        let mut elements = vec![];
-        for n in 0..node_list.length() {
-            let element = node_list.item(n).unwrap().dyn_into::<Element>().unwrap();
-            elements.push(element);
-        }
+    // 始まっていて、止めるまで同じ動きを繰り返すだけのアニメーション
+    // 書き込む値は経過時間だけで決まるので、途中のフレームを飛ばしても結果が変わらない
+    pub fn is_steady(&self) -> bool {
+        self.start_step != -1.0
+            && !self.block_scene_update
+            && self.cues.is_empty()
+            && self.typewriter.is_none()
+            && !self.tweens.is_empty()
+            && self
+                .tweens
+                .iter()
+                .all(|tween| tween.total_duration().is_none())
+    }
+
+    // tweens をまとめて動かし、すべて終わったら取り除かれる
+    pub fn create_tween(shared_elements: &SharedElements, tweens: Vec<Tween>) -> Animation {
         Animation {
+            document: shared_elements.document.clone(),
             args_i32: vec![],
             messages: vec![],
             block_scene_update: false,
             start_step: -1.0,
-            elements,
+            elements: vec![],
             span: AnimationSpan::None,
-            animation_func: move |animation, _, step| {
+            tweens,
+            release_block_at: None,
+            cues: vec![],
+            typewriter: None,
+            buffer_input: false,
+            animation_func: |animation, _, step| {
                 animation.init_step(step);
                 let gap = animation.get_step_gap(step);
-                let gap_sin = (gap / 250_f64).sin() / 2_f64 + 0.5;
-                for element in animation.elements.iter() {
-                    element
-                        .set_attribute("fill-opacity", &*gap_sin.to_string())
-                        .unwrap();
+                for tween in animation.tweens.iter() {
+                    tween.apply(gap);
                 }
-
-                false
+                if let Some(release_block_at) = animation.release_block_at {
+                    if gap >= release_block_at {
+                        animation.block_scene_update = false;
+                    }
+                }
+                animation.tweens.iter().all(|tween| tween.is_finished(gap))
             },
         }
     }
 
-    pub fn create_fade_out_in() -> Animation {
-        Animation::create_fade_out_in_with_span(AnimationSpan::FadeOutInDefault)
+    // 終わるまで (release_block_at を指定した場合はその時点まで) シーン遷移を待たせる
+    pub fn blocking(mut self, release_block_at: Option<f64>) -> Animation {
+        self.block_scene_update = true;
+        self.release_block_at = release_block_at;
+        self
     }
-    pub fn create_fade_out_in_with_span(span: AnimationSpan) -> Animation {
-        Animation {
-            args_i32: vec![],
-            messages: vec![],
-            block_scene_update: true,
-            start_step: -1.0,
-            elements: vec![web_sys::window()
-                .unwrap()
-                .document()
-                .unwrap()
-                .query_selector("#fader rect")
-                .unwrap()
-                .unwrap()],
-            span,
-            animation_func: move |animation, _, step| {
-                animation.init_step(step);
-                let span_f64 = animation.span.clone() as i32 as f64;
-                let half_span = span_f64 / 2.0;
-                let gap = animation.get_step_gap(step);
-                if gap < half_span {
-                    animation
-                        .elements
-                        .get(0)
-                        .unwrap()
-                        .set_attribute("fill-opacity", &*(gap / half_span).to_string())
-                        .expect("TODO: panic message");
-                } else {
-                    animation
-                        .elements
-                        .get(0)
-                        .unwrap()
-                        .set_attribute(
-                            "fill-opacity",
-                            &*(1.0 - ((gap - half_span) / half_span)).to_string(),
-                        )
-                        .unwrap();
-                }
 
-                if animation.block_scene_update && animation.start_step + half_span < step {
-                    animation.block_scene_update = false;
-                }
-                if gap > half_span * 2.0 {
-                    console_log!("animation end");
-                    animation
-                        .elements
-                        .get(0)
-                        .unwrap()
-                        .set_attribute("fill-opacity", "0")
-                        .unwrap();
-                    true
-                } else {
-                    false
-                }
-            },
+    pub fn buffering_input(mut self) -> Animation {
+        self.buffer_input = true;
+        self
+    }
+
+    pub fn on(mut self, cue: Cue, callback: impl Fn(&mut State) + 'static) -> Animation {
+        self.cues.push((cue, Rc::new(callback)));
+        self
+    }
+
+    pub fn on_midpoint(self, callback: impl Fn(&mut State) + 'static) -> Animation {
+        self.on(Cue::Midpoint, callback)
+    }
+
+    pub fn on_complete(self, callback: impl Fn(&mut State) + 'static) -> Animation {
+        self.on(Cue::Complete, callback)
+    }
+
+    // tweens で決まる長さ (繰り返し続けるものや tweens を持たないものは None)
+    pub fn duration(&self) -> Option<f64> {
+        let mut duration: Option<f64> = None;
+        for tween in self.tweens.iter() {
+            let total_duration = tween.total_duration()?;
+            duration = Some(duration.map_or(total_duration, |d| d.max(total_duration)));
         }
+        duration
     }
-    pub fn create_message(message: String) -> Animation {
-        let document = web_sys::window().unwrap().document().unwrap();
-        let elements = vec![
-            document.get_element_by_id("message").unwrap(),
-            document.get_element_by_id("message-1").unwrap(),
-            document.get_element_by_id("message-2").unwrap(),
-        ];
+
+    // 時点に達した cues を取り出して callbacks に積む (終わった時は残りもすべて)
+    pub fn take_due_cues(
+        &mut self,
+        step: f64,
+        finished: bool,
+        callbacks: &mut Vec<AnimationCallback>,
+    ) {
+        let elapsed = if self.start_step == -1.0 {
+            None
+        } else {
+            Some(self.get_step_gap(step))
+        };
+        let midpoint = self.duration().map(|duration| duration / 2.0);
+        self.cues.retain(|(cue, callback)| {
+            let due = finished
+                || match (cue, elapsed) {
+                    (Cue::At(at), Some(elapsed)) => elapsed >= *at,
+                    (Cue::Midpoint, Some(elapsed)) => midpoint.is_some_and(|m| elapsed >= m),
+                    _ => false,
+                };
+            if due {
+                callbacks.push(callback.clone());
+            }
+            !due
+        });
+    }
+
+    pub fn always_blink(shared_elements: &SharedElements) -> Animation {
+        let elements = shared_elements.document.query_selector_all(".always-blink");
+        Animation::create_tween(
+            shared_elements,
+            vec![
+                Tween::attribute(elements, "fill-opacity", 0.0, 1.0, 250.0 * PI)
+                    .easing(Easing::EaseInOut)
+                    .forever(),
+            ],
+        )
+    }
+
+    pub fn create_fade_out_in(shared_elements: &SharedElements) -> Animation {
+        Animation::create_fade_out_in_with_span(shared_elements, AnimationSpan::FadeOutInDefault)
+    }
+    // 真っ暗になった時点でシーン遷移を反映させる
+    pub fn create_fade_out_in_with_span(
+        shared_elements: &SharedElements,
+        span: AnimationSpan,
+    ) -> Animation {
+        let half_span = span as i32 as f64 / 2.0;
+        let fader = shared_elements.fader.clone();
+        let mut animation = Animation::create_tween(
+            shared_elements,
+            vec![Tween::attribute(vec![fader], "fill-opacity", 0.0, 1.0, half_span).yoyo()],
+        )
+        .blocking(Some(half_span));
+        animation.span = span;
+        animation
+    }
+    pub fn create_message(shared_elements: &SharedElements, message: String) -> Animation {
+        let [line_1, line_2] = shared_elements.message_lines.clone();
+        let elements = vec![shared_elements.message.element.clone(), line_1, line_2];
         Animation {
+            document: shared_elements.document.clone(),
             args_i32: vec![],
             messages: vec![message.to_owned()],
             block_scene_update: true,
             start_step: -1.0,
             elements,
             span: AnimationSpan::None,
-            animation_func: |animation, references, _| {
-                if references.borrow_mut().has_block_message {
-                    return false;
-                }
-                if animation.messages.is_empty() {
-                    animation.block_scene_update = false;
-                    return true;
-                }
-                animation.block_scene_update = true;
-                references.borrow_mut().has_block_message = true;
-                animation.elements[0]
-                    .set_attribute("display", "block")
-                    .unwrap();
-                animation.elements[1].set_inner_html(&animation.messages.remove(0));
-                animation.elements[2].set_inner_html("");
-                return false;
+            tweens: vec![],
+            release_block_at: None,
+            cues: vec![],
+            typewriter: None,
+            buffer_input: false,
+            animation_func: |animation, references, step| {
+                Animation::reveal_messages(animation, references, step, 1)
             },
         }
     }
-    pub fn create_multi_line_messages(messages: Vec<String>) -> Animation {
-        let document = web_sys::window().unwrap().document().unwrap();
+    pub fn create_multi_line_messages(
+        shared_elements: &SharedElements,
+        messages: Vec<String>,
+    ) -> Animation {
+        let [line_1, line_2] = shared_elements.message_lines.clone();
         let elements = vec![
-            document.get_element_by_id("message").unwrap(),
-            document.get_element_by_id("message-1").unwrap(),
-            document.get_element_by_id("message-2").unwrap(),
-            document
-                .get_element_by_id("has-continuous-message")
-                .unwrap(),
+            shared_elements.message.element.clone(),
+            line_1,
+            line_2,
+            shared_elements.has_continuous_message.clone(),
         ];
         Animation {
+            document: shared_elements.document.clone(),
             args_i32: vec![],
             messages,
             block_scene_update: true,
             start_step: -1.0,
             elements,
             span: AnimationSpan::None,
-            animation_func: |animation, references, _| {
-                if references.borrow_mut().has_block_message {
-                    return false;
-                }
-                if animation.messages.is_empty() {
-                    animation.block_scene_update = false;
-                    return true;
-                }
-                animation.block_scene_update = true;
-                references.borrow_mut().has_block_message = true;
-                animation.elements[0]
-                    .set_attribute("display", "block")
-                    .unwrap();
-                animation.elements[1].set_inner_html(&animation.messages.remove(0));
-                if !animation.messages.is_empty() {
-                    animation.elements[2].set_inner_html(&animation.messages.remove(0));
-                } else {
-                    animation.elements[2].set_inner_html("");
-                }
-                let has_continuous_message = !animation.messages.is_empty();
-                let display = if has_continuous_message {
-                    "block"
-                } else {
-                    "none"
-                };
-                animation.elements[3]
-                    .set_attribute("display", display)
-                    .unwrap();
-                (*references.borrow_mut()).has_continuous_message = has_continuous_message;
-                return false;
+            tweens: vec![],
+            release_block_at: None,
+            cues: vec![],
+            typewriter: None,
+            buffer_input: false,
+            animation_func: |animation, references, step| {
+                Animation::reveal_messages(animation, references, step, 2)
             },
         }
     }
-    pub fn create_move(start_x: i32, start_y: i32, end_x: i32, end_y: i32) -> Animation {
-        let document = web_sys::window().unwrap().document().unwrap();
-        let wrapper_element = document.query_selector("#field-wrapper").unwrap().unwrap();
-        let character_cursor_element = document
-            .query_selector("#character-cursor")
-            .unwrap()
-            .unwrap();
-        let character_direction_element = document
-            .query_selector("#character-direction")
-            .unwrap()
-            .unwrap();
+
+    // messages を lines_per_page 行ずつ 1 文字ずつ表示する
+    // 出し終えるまでの決定キーは残りを一度に表示し、出し終えてからの決定キーで次のページに進む
+    fn reveal_messages(
+        animation: &mut Animation,
+        references: Rc<RefCell<References>>,
+        step: f64,
+        lines_per_page: usize,
+    ) -> bool {
+        if let Some(typewriter) = animation.typewriter.as_mut() {
+            let mut references = references.borrow_mut();
+            if std::mem::take(&mut references.complete_message_requested) {
+                typewriter.complete();
+            }
+            let (markups, finished) = typewriter.update(step, references.text_speed);
+            for (index, element) in animation.elements[1..3].iter().enumerate() {
+                element.set_inner_html(markups.get(index).map_or("", |markup| markup.as_str()));
+            }
+            if finished {
+                animation.typewriter = None;
+                references.revealing_message = false;
+                // 続きがあることは出し終えてから示す
+                let has_continuous_message = !animation.messages.is_empty();
+                if let Some(indicator) = animation.elements.get(3) {
+                    let display = if has_continuous_message {
+                        "block"
+                    } else {
+                        "none"
+                    };
+                    indicator.set_attribute("display", display);
+                }
+                references.has_continuous_message = has_continuous_message;
+            }
+            return false;
+        }
+        if references.borrow_mut().has_block_message {
+            return false;
+        }
+        if animation.messages.is_empty() {
+            animation.block_scene_update = false;
+            return true;
+        }
+        animation.block_scene_update = true;
+        let count = lines_per_page.min(animation.messages.len());
+        let lines: Vec<String> = animation.messages.drain(..count).collect();
+        animation.typewriter = Some(Typewriter::new(&lines, step));
+        {
+            let mut references = references.borrow_mut();
+            references.has_block_message = true;
+            references.revealing_message = true;
+            references.complete_message_requested = false;
+        }
+        animation.elements[0].set_attribute("display", "block");
+        if let Some(indicator) = animation.elements.get(3) {
+            indicator.set_attribute("display", "none");
+        }
+        // 1 文字目はこのフレームで出す
+        Animation::reveal_messages(animation, references, step, lines_per_page)
+    }
+    // キャラクターを 1 マス分歩かせ、カメラも一緒に動かす (span は Walk か Dash)
+    // 歩いている間の入力は溜めておき、歩き終えてから処理させる
+    pub fn create_move(
+        shared_elements: &SharedElements,
+        camera: &Camera,
+        character_element: &Element,
+        from: Position,
+        to: Position,
+        span: AnimationSpan,
+    ) -> Animation {
+        let span = span as i32 as f64;
+        let walk = Tween::translate(
+            vec![character_element.clone()],
+            (from.x as f64, from.y as f64),
+            (to.x as f64, to.y as f64),
+            span,
+        );
+        camera
+            .follow_to(shared_elements, to, span, vec![walk])
+            .blocking(None)
+            .buffering_input()
+    }
+    // element を intensity px の幅で duration ミリ秒揺らし、translate(0, 0) に戻す
+    // 乱数は使わない (再生・巻き戻しで同じ揺れ方になるように)
+    pub fn create_shake(
+        shared_elements: &SharedElements,
+        element: Element,
+        intensity: i32,
+        duration: i32,
+    ) -> Animation {
         Animation {
-            args_i32: vec![start_x, start_y, end_x, end_y],
+            document: shared_elements.document.clone(),
+            args_i32: vec![intensity, duration],
             messages: vec![],
-            block_scene_update: true,
+            block_scene_update: false,
             start_step: -1.0,
-            elements: vec![
-                character_cursor_element,
-                character_direction_element,
-                wrapper_element,
-            ],
+            elements: vec![element],
             span: AnimationSpan::None,
+            tweens: vec![],
+            release_block_at: None,
+            cues: vec![],
+            typewriter: None,
+            buffer_input: false,
             animation_func: |animation, _, step| {
                 animation.init_step(step);
-                let scale = 150.0;
-                let gap = animation.get_step_gap(step).min(scale);
-                let start_x = animation.args_i32[0] as f64;
-                let start_y = animation.args_i32[1] as f64;
-                let end_x = animation.args_i32[2] as f64;
-                let end_y = animation.args_i32[3] as f64;
-                let step_x = start_x + ((end_x - start_x) / scale * gap);
-                let step_y = start_y + ((end_y - start_y) / scale * gap);
-                let character_cursor_element = &animation.elements[0];
-                let character_direction_element = &animation.elements[1];
-                let wrapper_element = &animation.elements[2];
-                character_cursor_element
-                    .set_attribute("x", &*step_x.to_string())
-                    .unwrap();
-                character_direction_element
-                    .set_attribute("x", &*step_x.to_string())
-                    .unwrap();
-                character_cursor_element
-                    .set_attribute("y", &*step_y.to_string())
-                    .unwrap();
-                character_direction_element
-                    .set_attribute("y", &*(step_y + 35.0).to_string())
-                    .unwrap();
-                let wrapper_translate_x = 360.0 - step_x;
-                let wrapper_translate_y = 280.0 - step_y;
-                wrapper_element
-                    .set_attribute(
-                        "transform",
-                        format!(
-                            "translate({}, {})",
-                            wrapper_translate_x, wrapper_translate_y
-                        )
-                        .as_str(),
-                    )
-                    .unwrap();
-                gap >= scale
+                let gap = animation.get_step_gap(step);
+                let intensity = animation.args_i32[0] as f64;
+                let duration = animation.args_i32[1] as f64;
+                if gap >= duration {
+                    animation.elements[0].set_attribute("transform", "translate(0, 0)");
+                    return true;
+                }
+                // 終わりに近づくほど小さく揺らす
+                let amplitude = intensity * (1.0 - gap / duration);
+                let x = (gap / 25.0 * PI).sin() * amplitude;
+                let y = (gap / 35.0 * PI).cos() * amplitude / 2.0;
+                animation.elements[0]
+                    .set_attribute("transform", &format!("translate({:.1}, {:.1})", x, y));
+                false
             },
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
+#[repr(i32)]
 pub enum AnimationSpan {
     FadeOutInDefault = 500,
     FadeOutInLong = 2000,
     FadeOutInMedium = 1000,
     EmoteDefault = 5000,
+    Walk = 150,
+    Dash = 75,
     None = 0,
 }
@@ -0,0 +1,260 @@
+use crate::engine::error::EngineError;
+use crate::features::animation::Animation;
+use crate::features::tween::Tween;
+use crate::svg::backend::{DomBackend, Element};
+use crate::svg::{Position, SharedElements};
+use std::cell::Cell;
+use std::rc::Rc;
+
+// マップの 1 マスの大きさ
+const TILE_SIZE: f64 = 40.0;
+
+// 画面に映す大きさ (フィールドの <svg> の width, height)
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Viewport {
+    // width, height が読めなければ index.html の既定の大きさにする
+    pub fn from_element(element: &Element) -> Viewport {
+        let default = Viewport::default();
+        let length = |name: &str, default: f64| {
+            element
+                .get_attribute(name)
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(default)
+        };
+        Viewport {
+            width: length("width", default.width),
+            height: length("height", default.height),
+        }
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            width: 800.0,
+            height: 600.0,
+        }
+    }
+}
+
+// カメラが映してよい範囲 (マップの地面)
+#[derive(Clone, Copy, Debug)]
+pub struct Bounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+// 画面の中央に映すマップ上の点と拡大率
+#[derive(Clone, Copy, Debug)]
+struct Framing {
+    focus: (f64, f64),
+    zoom: f64,
+}
+
+// フィールドのどこを画面の中央に映すか
+// 追いかける対象を中央に置き、地面の外は映さないようにずらす (地面が画面より小さければ地面を中央に置く)
+pub struct Camera {
+    viewport: Viewport,
+    // translate(x, y) scale(s) を書き込む (マップとキャラクターを含む)
+    element: Element,
+    // 揺らす時に動かす (element の外側)
+    shake_element: Element,
+    // 動かすアニメーションは終わった時に書き換える (途中で止められたら元のまま)
+    framing: Rc<Cell<Framing>>,
+    bounds: Option<Bounds>,
+}
+
+impl Camera {
+    pub fn new(document: &Rc<dyn DomBackend>) -> Result<Camera, EngineError> {
+        let element = |id: &str| {
+            document
+                .get_element_by_id(id)
+                .ok_or_else(|| EngineError::MissingElement(id.to_string()))
+        };
+        Ok(Camera::with_elements(
+            Viewport::from_element(&element("field")?),
+            element("field-camera")?,
+            element("field-shake")?,
+        ))
+    }
+
+    // 描画先に要素が無い時の代わり (どこにも繋がっていない要素に書き込む)
+    pub fn detached(document: &Rc<dyn DomBackend>) -> Camera {
+        Camera::with_elements(
+            Viewport::default(),
+            document.create_element("g"),
+            document.create_element("g"),
+        )
+    }
+
+    fn with_elements(viewport: Viewport, element: Element, shake_element: Element) -> Camera {
+        Camera {
+            viewport,
+            element,
+            shake_element,
+            framing: Rc::new(Cell::new(Framing {
+                focus: (viewport.width / 2.0, viewport.height / 2.0),
+                zoom: 1.0,
+            })),
+            bounds: None,
+        }
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+        self.bounds = bounds;
+    }
+
+    // 今の translate(x, y) と scale
+    pub fn transform(&self) -> (f64, f64, f64) {
+        self.transform_of(self.framing.get())
+    }
+
+    pub fn apply(&self) {
+        let (x, y, scale) = self.transform();
+        self.element.set_attribute(
+            "transform",
+            &format!("translate({}, {}) scale({})", x, y, scale),
+        );
+    }
+
+    // マスの中央を映す
+    pub fn follow(&mut self, position: Position) {
+        self.set_framing(|framing| framing.focus = Camera::tile_center(position));
+    }
+
+    pub fn set_zoom(&mut self, zoom: f64) {
+        self.set_framing(|framing| framing.zoom = zoom);
+    }
+
+    // 追いかける対象が position まで動く間、一緒に動かす (tweens は同じ長さで一緒に動かすもの)
+    pub fn follow_to(
+        &self,
+        shared_elements: &SharedElements,
+        position: Position,
+        duration: f64,
+        mut tweens: Vec<Tween>,
+    ) -> Animation {
+        let mut to = self.framing.get();
+        to.focus = Camera::tile_center(position);
+        tweens.push(Tween::translate_scale(
+            vec![self.element.clone()],
+            self.transform(),
+            self.transform_of(to),
+            duration,
+        ));
+        // 映す位置は歩き終えてから変える
+        let framing = self.framing.clone();
+        Animation::create_tween(shared_elements, tweens).on_complete(move |_| framing.set(to))
+    }
+
+    // 扉が開いた時などに画面を揺らす (シーン遷移は止めない)
+    pub fn shake(
+        &self,
+        shared_elements: &SharedElements,
+        intensity: i32,
+        duration: i32,
+    ) -> Animation {
+        Animation::create_shake(
+            shared_elements,
+            self.shake_element.clone(),
+            intensity,
+            duration,
+        )
+    }
+
+    fn set_framing(&mut self, change: impl FnOnce(&mut Framing)) {
+        let mut framing = self.framing.get();
+        change(&mut framing);
+        self.framing.set(framing);
+        self.apply();
+    }
+
+    fn transform_of(&self, framing: Framing) -> (f64, f64, f64) {
+        let zoom = framing.zoom;
+        let clamp = |focus: f64, viewport: f64, bounds: Option<(f64, f64)>| {
+            let translate = viewport / 2.0 - focus * zoom;
+            match bounds {
+                Some((start, length)) if length * zoom <= viewport => {
+                    (viewport - length * zoom) / 2.0 - start * zoom
+                }
+                Some((start, length)) => translate
+                    .min(-start * zoom)
+                    .max(viewport - (start + length) * zoom),
+                None => translate,
+            }
+        };
+        (
+            clamp(
+                framing.focus.0,
+                self.viewport.width,
+                self.bounds.map(|bounds| (bounds.x, bounds.width)),
+            ),
+            clamp(
+                framing.focus.1,
+                self.viewport.height,
+                self.bounds.map(|bounds| (bounds.y, bounds.height)),
+            ),
+            zoom,
+        )
+    }
+
+    fn tile_center(position: Position) -> (f64, f64) {
+        (
+            position.x as f64 + TILE_SIZE / 2.0,
+            position.y as f64 + TILE_SIZE / 2.0,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svg::backend::memory::MemoryBackend;
+
+    fn document(markup: &str) -> Rc<dyn DomBackend> {
+        Rc::new(MemoryBackend::from_markup(markup).unwrap())
+    }
+
+    #[test]
+    fn viewport_falls_back_to_default_size() {
+        let document = document(r#"<svg id="field" width="640"></svg>"#);
+        let viewport = Viewport::from_element(&document.get_element_by_id("field").unwrap());
+        assert_eq!((viewport.width, viewport.height), (640.0, 600.0));
+    }
+
+    #[test]
+    fn missing_elements_are_reported() {
+        let document = document(r#"<svg id="field" width="800" height="600"></svg>"#);
+        assert_eq!(
+            Camera::new(&document).err(),
+            Some(EngineError::MissingElement("field-camera".to_string()))
+        );
+        // 代わりのカメラも同じように動かせる
+        let mut camera = Camera::detached(&document);
+        camera.follow(Position::new(0, 0));
+        assert_eq!(camera.transform(), (380.0, 280.0, 1.0));
+    }
+
+    #[test]
+    fn framing_changes_only_when_tween_completes() {
+        let document = document(include_str!("../../index.html"));
+        let mut camera = Camera::new(&document).unwrap();
+        camera.follow(Position::new(0, 0));
+        let (shared_elements, _) = SharedElements::new(document.clone());
+        // 作っただけ (途中で止められた場合も同じ) では映す位置は変わらない
+        let walk = camera.follow_to(&shared_elements, Position::new(400, 400), 150.0, vec![]);
+        assert_eq!(camera.transform(), (380.0, 280.0, 1.0));
+        assert_eq!(walk.cues.len(), 1);
+    }
+}
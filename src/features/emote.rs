@@ -1,184 +1,103 @@
 use crate::features::animation::{Animation, AnimationSpan};
+use crate::svg::backend::Element;
+use crate::svg::SharedElements;
 use serde::{Deserialize, Serialize};
 
 impl Animation {
-    pub fn show_emote(message: EmoteMessage, own_emote: bool) -> Animation {
+    // parent_element に吹き出しを描く (自分のエモートは #field-character、ほかのプレイヤーは #field-wrapper)
+    // 位置は parent_element の中の座標で、自分のキャラクターは #field-character の原点にいる
+    pub fn show_emote(
+        shared_elements: &SharedElements,
+        parent_element: Element,
+        message: EmoteMessage,
+        own_emote: bool,
+    ) -> Animation {
         Animation {
+            document: shared_elements.document.clone(),
             args_i32: vec![if own_emote { 1 } else { -1 }],
             messages: vec![message.user_name, message.emote],
             block_scene_update: false,
             start_step: -1.0,
-            elements: vec![],
+            elements: vec![parent_element],
             span: AnimationSpan::EmoteDefault,
+            tweens: vec![],
+            release_block_at: None,
+            cues: vec![],
+            typewriter: None,
+            buffer_input: false,
             animation_func: |animation, _, step| {
                 let own_emote = animation.args_i32[0] == 1;
-                let document = web_sys::window().unwrap().document().unwrap();
-                let another_emote_selector = format!(".emote.user-name-{}", animation.messages[0]);
-                let selector_str = if own_emote {
-                    ".emote.character"
+                let document = animation.document.clone();
+                let owner_class_name = if own_emote {
+                    "character".to_string()
                 } else {
-                    another_emote_selector.as_str()
+                    format!("user-name-{}", animation.messages[0])
                 };
-                if let Some(element) = document.query_selector(selector_str).unwrap() {
-                    element.remove();
-                    if own_emote {
-                        document
-                            .query_selector(".emote-background.character")
-                            .unwrap()
-                            .unwrap()
-                            .remove();
-                        document
-                            .query_selector(".emote-background-arrow.character")
-                            .unwrap()
-                            .unwrap()
-                            .remove();
-                    } else {
-                        document
-                            .query_selector(
-                                format!(".emote-background.user-name-{}", animation.messages[0])
-                                    .as_str(),
-                            )
-                            .unwrap()
-                            .unwrap()
-                            .remove();
-                        document
-                            .query_selector(
-                                format!(
-                                    ".emote-background-arrow.user-name-{}",
-                                    animation.messages[0]
-                                )
-                                .as_str(),
-                            )
-                            .unwrap()
-                            .unwrap()
-                            .remove();
+                // 前のフレームで描いた吹き出しを消す
+                for class_name in ["emote", "emote-background", "emote-background-arrow"] {
+                    let selectors = format!(".{}.{}", class_name, owner_class_name);
+                    if let Some(element) = document.query_selector(&selectors) {
+                        element.remove();
                     }
-                };
+                }
                 animation.init_step(step);
                 let gap = animation.get_step_gap(step);
-                let span = animation.span.clone() as i32 as f64;
+                let span = animation.span as i32 as f64;
                 if gap > span {
                     return true;
                 }
-                let another_rect_selector =
-                    format!(".online-user.user-name-{}", animation.messages[0]);
-                if let Some(element) = document
-                    .query_selector(if own_emote {
-                        "rect.character"
-                    } else {
-                        another_rect_selector.as_str()
-                    })
-                    .unwrap()
-                {
-                    let x: f64 = element.get_attribute("x").unwrap().parse().unwrap();
-                    let y: f64 = element.get_attribute("y").unwrap().parse().unwrap();
-                    let emote_element = web_sys::window()
-                        .unwrap()
-                        .document()
-                        .unwrap()
-                        .create_element_ns(Some("http://www.w3.org/2000/svg"), "text")
-                        .unwrap();
-                    emote_element.set_inner_html(animation.messages[1].as_str());
-                    emote_element
-                        .set_attribute("x", (x + 1.5_f64).to_string().as_str())
-                        .unwrap();
-                    emote_element
-                        .set_attribute("y", (y - 19.5_f64).to_string().as_str())
-                        .unwrap();
-                    emote_element.set_attribute("font-size", "30").unwrap();
-                    let emote_background = web_sys::window()
-                        .unwrap()
-                        .document()
-                        .unwrap()
-                        .create_element_ns(Some("http://www.w3.org/2000/svg"), "rect")
-                        .unwrap();
-                    emote_background
-                        .set_attribute("x", x.to_string().as_str())
-                        .unwrap();
-                    emote_background
-                        .set_attribute("y", (y - 50_f64).to_string().as_str())
-                        .unwrap();
-                    emote_background.set_attribute("rx", "3").unwrap();
-                    emote_background.set_attribute("fill", "white").unwrap();
-                    emote_background.set_attribute("width", "40").unwrap();
-                    emote_background.set_attribute("height", "39").unwrap();
-                    let emote_background_arrow = web_sys::window()
-                        .unwrap()
-                        .document()
-                        .unwrap()
-                        .create_element_ns(Some("http://www.w3.org/2000/svg"), "polygon")
-                        .unwrap();
-                    let point_a = format!("{} {}", x + 34.0, y - 13.0);
-                    let point_b = format!("{} {}", x + 25.0, y - 3.0);
-                    let point_c = format!("{} {}", x + 25.0, y - 13.0);
-                    emote_background_arrow
-                        .set_attribute(
-                            "points",
-                            format!("{}, {}, {}", point_a, point_b, point_c).as_str(),
-                        )
-                        .unwrap();
-                    emote_background_arrow
-                        .set_attribute("fill", "white")
-                        .unwrap();
-                    let parent_element = if own_emote {
-                        emote_element
-                            .class_list()
-                            .add_2("emote", "character")
-                            .unwrap();
-                        emote_background
-                            .class_list()
-                            .add_2("emote-background", "character")
-                            .unwrap();
-                        emote_background_arrow
-                            .class_list()
-                            .add_2("emote-background-arrow", "character")
-                            .unwrap();
-                        document.get_element_by_id("field").unwrap()
-                    } else {
-                        emote_element
-                            .class_list()
-                            .add_2(
-                                "emote",
-                                format!("user-name-{}", animation.messages[0]).as_str(),
-                            )
-                            .unwrap();
-                        emote_background
-                            .class_list()
-                            .add_2(
-                                "emote-background",
-                                format!("user-name-{}", animation.messages[0]).as_str(),
-                            )
-                            .unwrap();
-                        emote_background_arrow
-                            .class_list()
-                            .add_2(
-                                "emote-background-arrow",
-                                format!("user-name-{}", animation.messages[0]).as_str(),
-                            )
-                            .unwrap();
-                        document.get_element_by_id("field-wrapper").unwrap()
-                    };
-                    if gap > span * 0.95 {
-                        let opacity = 1.0 - (gap - span * 0.95) / (span * 0.05);
-                        emote_background_arrow
-                            .set_attribute("fill-opacity", &*opacity.to_string())
-                            .unwrap();
-                        emote_background
-                            .set_attribute("fill-opacity", &*opacity.to_string())
-                            .unwrap();
-                        emote_element
-                            .set_attribute("fill-opacity", &*opacity.to_string())
-                            .unwrap();
-                    }
-                    parent_element
-                        .append_child(&*emote_background_arrow)
-                        .unwrap();
-                    parent_element.append_child(&*emote_background).unwrap();
-                    parent_element.append_child(&*emote_element).unwrap();
-                    false
+                let rect_selector = if own_emote {
+                    "rect.character".to_string()
                 } else {
-                    true
+                    format!(".online-user.user-name-{}", animation.messages[0])
+                };
+                // 相手がいなくなった (位置が読めない) 時は吹き出しも消す
+                let position = document.query_selector(&rect_selector).and_then(|element| {
+                    let x = element.get_attribute("x")?.parse::<f64>().ok()?;
+                    let y = element.get_attribute("y")?.parse::<f64>().ok()?;
+                    Some((x, y))
+                });
+                let Some((x, y)) = position else {
+                    return true;
+                };
+                let emote_element = document.create_element("text");
+                emote_element.set_inner_html(animation.messages[1].as_str());
+                emote_element.set_attribute("x", (x + 1.5_f64).to_string().as_str());
+                emote_element.set_attribute("y", (y - 19.5_f64).to_string().as_str());
+                emote_element.set_attribute("font-size", "30");
+                let emote_background = document.create_element("rect");
+                emote_background.set_attribute("x", x.to_string().as_str());
+                emote_background.set_attribute("y", (y - 50_f64).to_string().as_str());
+                emote_background.set_attribute("rx", "3");
+                emote_background.set_attribute("fill", "white");
+                emote_background.set_attribute("width", "40");
+                emote_background.set_attribute("height", "39");
+                let emote_background_arrow = document.create_element("polygon");
+                let point_a = format!("{} {}", x + 34.0, y - 13.0);
+                let point_b = format!("{} {}", x + 25.0, y - 3.0);
+                let point_c = format!("{} {}", x + 25.0, y - 13.0);
+                emote_background_arrow.set_attribute(
+                    "points",
+                    format!("{}, {}, {}", point_a, point_b, point_c).as_str(),
+                );
+                emote_background_arrow.set_attribute("fill", "white");
+                emote_element.add_class("emote");
+                emote_element.add_class(&owner_class_name);
+                emote_background.add_class("emote-background");
+                emote_background.add_class(&owner_class_name);
+                emote_background_arrow.add_class("emote-background-arrow");
+                emote_background_arrow.add_class(&owner_class_name);
+                if gap > span * 0.95 {
+                    let opacity = 1.0 - (gap - span * 0.95) / (span * 0.05);
+                    emote_background_arrow.set_attribute("fill-opacity", &opacity.to_string());
+                    emote_background.set_attribute("fill-opacity", &opacity.to_string());
+                    emote_element.set_attribute("fill-opacity", &opacity.to_string());
                 }
+                let parent_element = &animation.elements[0];
+                parent_element.append_child(&emote_background_arrow);
+                parent_element.append_child(&emote_background);
+                parent_element.append_child(&emote_element);
+                false
             },
         }
     }
@@ -0,0 +1,454 @@
+use crate::engine::state::References;
+use crate::engine::state::State;
+use crate::features::animation::{Animation, AnimationCallback};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// アニメーションの組み合わせ方
+//   Track::sequence(vec![Track::play(a), Track::delay(200.0), Track::parallel(vec![...])])
+// 並びの中では、前のものが終わった次のフレームから次のものを動かす
+#[derive(Clone)]
+pub enum Track {
+    Play(Animation),
+    // 呼んだらすぐ次に進む
+    Call(AnimationCallback),
+    Sequence(Vec<Track>),
+    Parallel(Vec<Track>),
+    Delay {
+        duration: f64,
+        start_step: Option<f64>,
+    },
+    Repeat {
+        template: Box<Track>,
+        current: Box<Track>,
+        // None なら止めるまで繰り返す
+        remaining: Option<u32>,
+    },
+}
+
+impl Track {
+    pub fn play(animation: Animation) -> Track {
+        Track::Play(animation)
+    }
+
+    pub fn call(callback: impl Fn(&mut State) + 'static) -> Track {
+        Track::Call(Rc::new(callback))
+    }
+
+    pub fn sequence(tracks: Vec<Track>) -> Track {
+        Track::Sequence(tracks)
+    }
+
+    pub fn parallel(tracks: Vec<Track>) -> Track {
+        Track::Parallel(tracks)
+    }
+
+    pub fn delay(duration: f64) -> Track {
+        Track::Delay {
+            duration,
+            start_step: None,
+        }
+    }
+
+    // times 回繰り返す (0 回は 1 回として扱う)
+    pub fn repeat(self, times: u32) -> Track {
+        Track::Repeat {
+            template: Box::new(self.clone()),
+            current: Box::new(self),
+            remaining: Some(times.max(1)),
+        }
+    }
+
+    pub fn repeat_forever(self) -> Track {
+        Track::Repeat {
+            template: Box::new(self.clone()),
+            current: Box::new(self),
+            remaining: None,
+        }
+    }
+
+    // 終わったら true
+    // 呼ぶ時点に達したコールバックは callbacks に積む (State を借りられないので呼ぶのはエンジン)
+    pub fn update(
+        &mut self,
+        references: &Rc<RefCell<References>>,
+        step: f64,
+        callbacks: &mut Vec<AnimationCallback>,
+    ) -> bool {
+        match self {
+            Track::Play(animation) => {
+                let finished = (animation.animation_func)(animation, references.clone(), step);
+                animation.take_due_cues(step, finished, callbacks);
+                finished
+            }
+            Track::Call(callback) => {
+                callbacks.push(callback.clone());
+                true
+            }
+            Track::Sequence(tracks) => {
+                if let Some(track) = tracks.first_mut() {
+                    if track.update(references, step, callbacks) {
+                        tracks.remove(0);
+                    }
+                }
+                tracks.is_empty()
+            }
+            Track::Parallel(tracks) => {
+                tracks.retain_mut(|track| !track.update(references, step, callbacks));
+                tracks.is_empty()
+            }
+            Track::Delay {
+                duration,
+                start_step,
+            } => step - *start_step.get_or_insert(step) >= *duration,
+            Track::Repeat {
+                template,
+                current,
+                remaining,
+            } => {
+                if !current.update(references, step, callbacks) {
+                    return false;
+                }
+                match remaining {
+                    Some(1) => return true,
+                    Some(remaining) => *remaining -= 1,
+                    None => {}
+                }
+                *current = template.clone();
+                false
+            }
+        }
+    }
+
+    // 今動いているアニメーションのどれかがシーン遷移を止めているか
+    pub fn is_blocking(&self) -> bool {
+        !self.blocking_animations().is_empty()
+    }
+
+    // 今動いていて、シーン遷移を止めているアニメーション
+    pub fn blocking_animations(&self) -> Vec<&Animation> {
+        match self {
+            Track::Play(animation) if animation.block_scene_update => vec![animation],
+            Track::Sequence(tracks) => tracks
+                .first()
+                .map_or(vec![], |track| track.blocking_animations()),
+            Track::Parallel(tracks) => tracks
+                .iter()
+                .flat_map(|track| track.blocking_animations())
+                .collect(),
+            Track::Repeat { current, .. } => current.blocking_animations(),
+            Track::Play(_) | Track::Call(_) | Track::Delay { .. } => vec![],
+        }
+    }
+
+    // 止めるまで同じ動きを続けるだけで、コールバックも呼ばないか
+    pub fn is_steady(&self) -> bool {
+        match self {
+            Track::Play(animation) => animation.is_steady(),
+            // 先頭が終わらないので、後ろは始まらない
+            Track::Sequence(tracks) => tracks.first().is_some_and(|track| track.is_steady()),
+            Track::Parallel(tracks) => {
+                !tracks.is_empty() && tracks.iter().all(|track| track.is_steady())
+            }
+            Track::Repeat { current, .. } => current.is_steady(),
+            Track::Call(_) | Track::Delay { .. } => false,
+        }
+    }
+
+    // まだ終わっていないアニメーション (これから動くものも含む)
+    pub fn animations(&self) -> Vec<&Animation> {
+        match self {
+            Track::Play(animation) => vec![animation],
+            Track::Sequence(tracks) | Track::Parallel(tracks) => {
+                tracks.iter().flat_map(|track| track.animations()).collect()
+            }
+            Track::Call(_) | Track::Delay { .. } => vec![],
+            Track::Repeat { current, .. } => current.animations(),
+        }
+    }
+}
+
+impl From<Animation> for Track {
+    fn from(animation: Animation) -> Track {
+        Track::Play(animation)
+    }
+}
+
+// Timeline::cancel に渡して再生中のトラックを止める
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimelineHandle(usize);
+
+// 登録したトラックを並行して動かす
+pub struct Timeline {
+    tracks: Vec<(TimelineHandle, Track)>,
+    next_id: usize,
+}
+
+impl Timeline {
+    pub fn new() -> Timeline {
+        Timeline {
+            tracks: vec![],
+            next_id: 0,
+        }
+    }
+
+    // 順に動かすアニメーションを追加する
+    pub fn push(&mut self, animations: Vec<Animation>) -> TimelineHandle {
+        self.play(Track::sequence(
+            animations.into_iter().map(Track::play).collect(),
+        ))
+    }
+
+    pub fn play(&mut self, track: Track) -> TimelineHandle {
+        let handle = TimelineHandle(self.next_id);
+        self.next_id += 1;
+        self.tracks.push((handle, track));
+        handle
+    }
+
+    // 書き込み途中の属性やメッセージ表示の状態はそのまま残るので、必要なら呼び出し側で戻す
+    pub fn cancel(&mut self, handle: TimelineHandle) -> bool {
+        let len = self.tracks.len();
+        self.tracks
+            .retain(|(track_handle, _)| *track_handle != handle);
+        self.tracks.len() != len
+    }
+
+    pub fn is_playing(&self, handle: TimelineHandle) -> bool {
+        self.tracks
+            .iter()
+            .any(|(track_handle, _)| *track_handle == handle)
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        self.tracks.iter().any(|(_, track)| track.is_blocking())
+    }
+
+    // シーン遷移を止めているのが入力を溜めておけるアニメーション (歩行など) だけか
+    pub fn is_buffering_input(&self) -> bool {
+        let mut blocking_animations = self
+            .tracks
+            .iter()
+            .flat_map(|(_, track)| track.blocking_animations())
+            .peekable();
+        blocking_animations.peek().is_some()
+            && blocking_animations.all(|animation| animation.buffer_input)
+    }
+
+    // 次のフレームで何も始まらず、何も終わらない
+    pub fn is_steady(&self) -> bool {
+        self.tracks.iter().all(|(_, track)| track.is_steady())
+    }
+
+    // このフレームで呼ぶ時点に達したコールバックを返す
+    pub fn update(
+        &mut self,
+        references: &Rc<RefCell<References>>,
+        step: f64,
+    ) -> Vec<AnimationCallback> {
+        let mut callbacks = vec![];
+        self.tracks
+            .retain_mut(|(_, track)| !track.update(references, step, &mut callbacks));
+        callbacks
+    }
+
+    pub fn tracks(&self) -> impl Iterator<Item = &Track> {
+        self.tracks.iter().map(|(_, track)| track)
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::tween::Tween;
+    use crate::svg::backend::memory::MemoryBackend;
+    use crate::svg::backend::{DomBackend, Element};
+    use crate::svg::SharedElements;
+    use std::cell::Cell;
+
+    struct Stage {
+        shared_elements: SharedElements,
+        references: Rc<RefCell<References>>,
+        element: Element,
+    }
+
+    fn stage() -> Stage {
+        let document: Rc<dyn DomBackend> = Rc::new(MemoryBackend::new());
+        let element = document.create_element("rect");
+        let (shared_elements, _) = SharedElements::new(document);
+        Stage {
+            shared_elements,
+            references: Rc::new(RefCell::new(References {
+                has_block_message: false,
+                has_continuous_message: false,
+                revealing_message: false,
+                complete_message_requested: false,
+                text_speed: 0.0,
+            })),
+            element,
+        }
+    }
+
+    impl Stage {
+        // element の name を from から to まで duration ミリ秒で変える
+        fn tween(&self, name: &str, from: f64, to: f64, duration: f64) -> Track {
+            let tween = Tween::attribute(vec![self.element.clone()], name, from, to, duration);
+            Track::play(Animation::create_tween(&self.shared_elements, vec![tween]))
+        }
+
+        fn value(&self, name: &str) -> Option<String> {
+            self.element.get_attribute(name)
+        }
+    }
+
+    fn counter() -> (Rc<Cell<u32>>, Track) {
+        let count = Rc::new(Cell::new(0));
+        let counted = count.clone();
+        // テストでは State を用意しないので、積まれた数を数える
+        let track = Track::call(move |_| counted.set(counted.get() + 1));
+        (count, track)
+    }
+
+    // 1 フレーム進め、積まれたコールバックの数を返す
+    fn frame(timeline: &mut Timeline, stage: &Stage, step: f64) -> usize {
+        timeline.update(&stage.references, step).len()
+    }
+
+    #[test]
+    fn sequence_starts_next_track_after_previous_finishes() {
+        let stage = stage();
+        let mut timeline = Timeline::new();
+        let (_, call) = counter();
+        let handle = timeline.play(Track::sequence(vec![
+            stage.tween("x", 0.0, 100.0, 100.0),
+            stage.tween("y", 0.0, 10.0, 100.0),
+            call,
+        ]));
+        frame(&mut timeline, &stage, 0.0);
+        frame(&mut timeline, &stage, 50.0);
+        assert_eq!(stage.value("x").as_deref(), Some("50"));
+        assert_eq!(stage.value("y"), None);
+        frame(&mut timeline, &stage, 100.0);
+        assert_eq!(stage.value("x").as_deref(), Some("100"));
+        // 次のトラックは次のフレームを開始時刻にする
+        frame(&mut timeline, &stage, 120.0);
+        frame(&mut timeline, &stage, 170.0);
+        assert_eq!(stage.value("y").as_deref(), Some("5"));
+        assert_eq!(frame(&mut timeline, &stage, 220.0), 0);
+        assert_eq!(frame(&mut timeline, &stage, 236.0), 1);
+        assert!(!timeline.is_playing(handle));
+    }
+
+    #[test]
+    fn parallel_runs_together_until_longest_finishes() {
+        let stage = stage();
+        let mut timeline = Timeline::new();
+        let handle = timeline.play(Track::parallel(vec![
+            stage.tween("x", 0.0, 100.0, 100.0),
+            stage.tween("y", 0.0, 100.0, 200.0),
+        ]));
+        frame(&mut timeline, &stage, 0.0);
+        frame(&mut timeline, &stage, 100.0);
+        assert_eq!(stage.value("x").as_deref(), Some("100"));
+        assert_eq!(stage.value("y").as_deref(), Some("50"));
+        assert!(timeline.is_playing(handle));
+        frame(&mut timeline, &stage, 200.0);
+        assert_eq!(stage.value("y").as_deref(), Some("100"));
+        assert!(!timeline.is_playing(handle));
+    }
+
+    #[test]
+    fn delay_waits_from_its_first_frame() {
+        let stage = stage();
+        let mut timeline = Timeline::new();
+        let (_, call) = counter();
+        timeline.play(Track::sequence(vec![Track::delay(200.0), call]));
+        assert_eq!(frame(&mut timeline, &stage, 1000.0), 0);
+        assert_eq!(frame(&mut timeline, &stage, 1199.0), 0);
+        // 待ち終えたフレームの次でコールバックが積まれる
+        assert_eq!(frame(&mut timeline, &stage, 1200.0), 0);
+        assert_eq!(frame(&mut timeline, &stage, 1216.0), 1);
+        assert_eq!(frame(&mut timeline, &stage, 1232.0), 0);
+    }
+
+    #[test]
+    fn repeat_replays_from_the_start() {
+        let stage = stage();
+        let mut timeline = Timeline::new();
+        let handle = timeline.play(stage.tween("x", 0.0, 10.0, 100.0).repeat(2));
+        frame(&mut timeline, &stage, 0.0);
+        frame(&mut timeline, &stage, 100.0);
+        assert_eq!(stage.value("x").as_deref(), Some("10"));
+        assert!(timeline.is_playing(handle));
+        // 2 回目は最初の値から
+        frame(&mut timeline, &stage, 120.0);
+        assert_eq!(stage.value("x").as_deref(), Some("0"));
+        frame(&mut timeline, &stage, 220.0);
+        assert!(!timeline.is_playing(handle));
+
+        let (_, call) = counter();
+        let handle = timeline.play(call.repeat(3));
+        let calls: usize = (0..3)
+            .map(|frame_index| frame(&mut timeline, &stage, 300.0 + frame_index as f64))
+            .sum();
+        assert_eq!(calls, 3);
+        assert!(!timeline.is_playing(handle));
+    }
+
+    #[test]
+    fn repeat_forever_runs_until_cancelled() {
+        let stage = stage();
+        let mut timeline = Timeline::new();
+        let handle = timeline.play(stage.tween("x", 0.0, 10.0, 100.0).repeat_forever());
+        let other = timeline.play(stage.tween("y", 0.0, 10.0, 100.0));
+        for frame_index in 0..50 {
+            frame(&mut timeline, &stage, frame_index as f64 * 60.0);
+        }
+        assert!(timeline.is_playing(handle));
+        assert!(timeline.cancel(handle));
+        assert!(!timeline.is_playing(handle));
+        // 止めたトラックはもう書き込まない
+        let x = stage.value("x");
+        frame(&mut timeline, &stage, 5000.0);
+        assert_eq!(stage.value("x"), x);
+        assert!(!timeline.cancel(handle));
+        assert!(!timeline.cancel(other));
+    }
+
+    #[test]
+    fn buffering_input_only_while_all_blocking_animations_buffer() {
+        let stage = stage();
+        let mut timeline = Timeline::new();
+        let tween = |duration| {
+            let tween = Tween::attribute(vec![stage.element.clone()], "x", 0.0, 1.0, duration);
+            Animation::create_tween(&stage.shared_elements, vec![tween])
+        };
+        // シーン遷移を止めないアニメーションだけなら溜めない
+        timeline.play(Track::play(tween(1000.0)));
+        assert!(!timeline.is_buffering_input());
+        let walk = timeline.play(Track::play(tween(100.0).blocking(None).buffering_input()));
+        assert!(timeline.is_blocking());
+        assert!(timeline.is_buffering_input());
+        let message = timeline.play(Track::sequence(vec![
+            Track::delay(50.0),
+            Track::play(tween(100.0).blocking(None)),
+        ]));
+        // 並びの先頭がまだ Delay なので、メッセージは止めていない
+        assert!(timeline.is_buffering_input());
+        frame(&mut timeline, &stage, 0.0);
+        frame(&mut timeline, &stage, 50.0);
+        frame(&mut timeline, &stage, 66.0);
+        assert!(!timeline.is_buffering_input());
+        timeline.cancel(message);
+        assert!(timeline.is_buffering_input());
+        timeline.cancel(walk);
+        assert!(!timeline.is_blocking());
+        assert!(!timeline.is_buffering_input());
+    }
+}
@@ -0,0 +1,343 @@
+use crate::svg::backend::Element;
+use std::f64::consts::PI;
+
+// 経過の割合 t (0.0〜1.0) から進み具合を求める
+#[derive(Clone, Copy, Debug)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    // 終わり際に弾む
+    Bounce,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => (1.0 - (PI * t).cos()) / 2.0,
+            Easing::Bounce => {
+                let (n, d) = (7.5625, 2.75);
+                if t < 1.0 / d {
+                    n * t * t
+                } else if t < 2.0 / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+// 値を書き込む先
+#[derive(Clone, Debug)]
+pub enum Property {
+    // 数値の属性 (fill-opacity, x など)
+    Attribute(String),
+    // transform="translate(x, y)"
+    Translate,
+    // transform="scale(s)"
+    Scale,
+    // transform="translate(x, y) scale(s)"
+    TranslateScale,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Repeat {
+    Once,
+    // from → to → from で 1 回
+    Yoyo,
+    // from → to → from を繰り返し続ける
+    Forever,
+}
+
+// elements の property を from から to まで duration ミリ秒かけて変える
+// Translate は [x, y]、TranslateScale は [x, y, s]、それ以外は [値] を渡す
+#[derive(Clone)]
+pub struct Tween {
+    elements: Vec<Element>,
+    property: Property,
+    from: Vec<f64>,
+    to: Vec<f64>,
+    duration: f64,
+    delay: f64,
+    easing: Easing,
+    repeat: Repeat,
+}
+
+impl Tween {
+    fn new(
+        elements: Vec<Element>,
+        property: Property,
+        from: Vec<f64>,
+        to: Vec<f64>,
+        duration: f64,
+    ) -> Tween {
+        Tween {
+            elements,
+            property,
+            from,
+            to,
+            duration,
+            delay: 0.0,
+            easing: Easing::Linear,
+            repeat: Repeat::Once,
+        }
+    }
+
+    pub fn attribute(
+        elements: Vec<Element>,
+        name: &str,
+        from: f64,
+        to: f64,
+        duration: f64,
+    ) -> Tween {
+        Tween::new(
+            elements,
+            Property::Attribute(name.to_string()),
+            vec![from],
+            vec![to],
+            duration,
+        )
+    }
+
+    pub fn translate(
+        elements: Vec<Element>,
+        from: (f64, f64),
+        to: (f64, f64),
+        duration: f64,
+    ) -> Tween {
+        Tween::new(
+            elements,
+            Property::Translate,
+            vec![from.0, from.1],
+            vec![to.0, to.1],
+            duration,
+        )
+    }
+
+    pub fn scale(elements: Vec<Element>, from: f64, to: f64, duration: f64) -> Tween {
+        Tween::new(elements, Property::Scale, vec![from], vec![to], duration)
+    }
+
+    pub fn translate_scale(
+        elements: Vec<Element>,
+        from: (f64, f64, f64),
+        to: (f64, f64, f64),
+        duration: f64,
+    ) -> Tween {
+        Tween::new(
+            elements,
+            Property::TranslateScale,
+            vec![from.0, from.1, from.2],
+            vec![to.0, to.1, to.2],
+            duration,
+        )
+    }
+
+    pub fn easing(mut self, easing: Easing) -> Tween {
+        self.easing = easing;
+        self
+    }
+
+    // 開始を遅らせる (その間は何も書き込まない)
+    pub fn delay(mut self, delay: f64) -> Tween {
+        self.delay = delay;
+        self
+    }
+
+    pub fn yoyo(mut self) -> Tween {
+        self.repeat = Repeat::Yoyo;
+        self
+    }
+
+    pub fn forever(mut self) -> Tween {
+        self.repeat = Repeat::Forever;
+        self
+    }
+
+    // 遅らせた分も含めた長さ (繰り返し続けるものは None)
+    pub fn total_duration(&self) -> Option<f64> {
+        match self.repeat {
+            Repeat::Once => Some(self.delay + self.duration),
+            Repeat::Yoyo => Some(self.delay + self.duration * 2.0),
+            Repeat::Forever => None,
+        }
+    }
+
+    pub fn is_finished(&self, elapsed: f64) -> bool {
+        match self.total_duration() {
+            Some(total_duration) => elapsed >= total_duration,
+            None => false,
+        }
+    }
+
+    // 経過時間 elapsed での値 (開始前は None)
+    pub fn value_at(&self, elapsed: f64) -> Option<Vec<f64>> {
+        let elapsed = elapsed - self.delay;
+        if elapsed < 0.0 {
+            return None;
+        }
+        let duration = self.duration.max(f64::EPSILON);
+        let t = match self.repeat {
+            Repeat::Once => elapsed / duration,
+            Repeat::Yoyo | Repeat::Forever => {
+                let round = match self.repeat {
+                    Repeat::Yoyo => (elapsed / duration).min(2.0),
+                    _ => (elapsed / duration) % 2.0,
+                };
+                if round <= 1.0 {
+                    round
+                } else {
+                    2.0 - round
+                }
+            }
+        };
+        let progress = self.easing.apply(t);
+        Some(
+            self.from
+                .iter()
+                .zip(self.to.iter())
+                .map(|(from, to)| from + (to - from) * progress)
+                .collect(),
+        )
+    }
+
+    pub fn apply(&self, elapsed: f64) {
+        let values = match self.value_at(elapsed) {
+            Some(values) => values,
+            None => return,
+        };
+        let (name, value) = match &self.property {
+            Property::Attribute(name) => (name.as_str(), values[0].to_string()),
+            Property::Translate => (
+                "transform",
+                format!("translate({}, {})", values[0], values[1]),
+            ),
+            Property::Scale => ("transform", format!("scale({})", values[0])),
+            Property::TranslateScale => (
+                "transform",
+                format!(
+                    "translate({}, {}) scale({})",
+                    values[0], values[1], values[2]
+                ),
+            ),
+        };
+        for element in self.elements.iter() {
+            element.set_attribute(name, &value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svg::backend::memory::MemoryBackend;
+    use crate::svg::backend::DomBackend;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn value(tween: &Tween, elapsed: f64) -> f64 {
+        tween.value_at(elapsed).unwrap()[0]
+    }
+
+    #[test]
+    fn easings_start_at_0_and_end_at_1() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::Bounce,
+        ] {
+            assert_close(easing.apply(0.0), 0.0);
+            assert_close(easing.apply(1.0), 1.0);
+            // 範囲外は端に揃える
+            assert_close(easing.apply(-0.5), 0.0);
+            assert_close(easing.apply(1.5), 1.0);
+        }
+        assert_close(Easing::EaseIn.apply(0.5), 0.25);
+        assert_close(Easing::EaseOut.apply(0.5), 0.75);
+        assert_close(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn once_waits_for_delay_and_stops_at_to() {
+        let tween = Tween::attribute(vec![], "x", 10.0, 20.0, 100.0).delay(50.0);
+        assert_eq!(tween.value_at(49.0), None);
+        assert_close(value(&tween, 50.0), 10.0);
+        assert_close(value(&tween, 100.0), 15.0);
+        assert_close(value(&tween, 150.0), 20.0);
+        assert_close(value(&tween, 500.0), 20.0);
+        assert_eq!(tween.total_duration(), Some(150.0));
+        assert!(!tween.is_finished(149.0));
+        assert!(tween.is_finished(150.0));
+    }
+
+    #[test]
+    fn yoyo_goes_to_and_back_once() {
+        let tween = Tween::attribute(vec![], "fill-opacity", 0.0, 1.0, 100.0).yoyo();
+        assert_close(value(&tween, 0.0), 0.0);
+        assert_close(value(&tween, 50.0), 0.5);
+        assert_close(value(&tween, 100.0), 1.0);
+        assert_close(value(&tween, 150.0), 0.5);
+        assert_close(value(&tween, 200.0), 0.0);
+        // 戻った後は from のまま
+        assert_close(value(&tween, 350.0), 0.0);
+        assert_eq!(tween.total_duration(), Some(200.0));
+        assert!(tween.is_finished(200.0));
+    }
+
+    #[test]
+    fn forever_keeps_going_back_and_forth() {
+        let tween = Tween::attribute(vec![], "fill-opacity", 0.0, 1.0, 100.0).forever();
+        assert_close(value(&tween, 100.0), 1.0);
+        assert_close(value(&tween, 200.0), 0.0);
+        assert_close(value(&tween, 250.0), 0.5);
+        assert_close(value(&tween, 1300.0), 1.0);
+        assert_eq!(tween.total_duration(), None);
+        assert!(!tween.is_finished(1_000_000.0));
+    }
+
+    #[test]
+    fn apply_writes_transforms() {
+        let document = MemoryBackend::new();
+        let element = document.create_element("g");
+        let tween = Tween::translate_scale(
+            vec![element.clone()],
+            (0.0, 0.0, 1.0),
+            (40.0, -80.0, 2.0),
+            100.0,
+        )
+        .delay(10.0);
+        tween.apply(0.0);
+        assert_eq!(element.get_attribute("transform"), None);
+        tween.apply(60.0);
+        assert_eq!(
+            element.get_attribute("transform").as_deref(),
+            Some("translate(20, -40) scale(1.5)")
+        );
+        Tween::translate(vec![element.clone()], (0.0, 0.0), (40.0, 0.0), 100.0).apply(100.0);
+        assert_eq!(
+            element.get_attribute("transform").as_deref(),
+            Some("translate(40, 0)")
+        );
+    }
+}
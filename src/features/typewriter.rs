@@ -0,0 +1,212 @@
+// メッセージを 1 文字ずつ表示する
+// 文中に次の制御コードを書ける (それ以外の [..] はそのまま表示する)
+//   [wait=500]        次の文字を出す前に 500 ミリ秒止める
+//   [color=#ffd700]   ここから [/color] までの文字色を変える (#rgb, #rrggbb, 色の名前だけ)
+
+// 1 文字あたりのミリ秒 (0 なら一度に表示する)
+pub const DEFAULT_TEXT_SPEED: f64 = 30.0;
+
+#[derive(Clone, Debug)]
+struct Glyph {
+    ch: char,
+    color: Option<String>,
+    // この文字を出す前に止める時間
+    wait: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct TypewriterLine {
+    glyphs: Vec<Glyph>,
+    // 最後の文字の後ろに書かれた wait
+    trailing_wait: f64,
+}
+
+impl TypewriterLine {
+    pub fn parse(text: &str) -> TypewriterLine {
+        let mut glyphs = vec![];
+        let mut color: Option<String> = None;
+        let mut wait = 0.0;
+        let mut rest = text;
+        while let Some(ch) = rest.chars().next() {
+            if ch == '[' {
+                if let Some(end) = rest.find(']') {
+                    let code = &rest[1..end];
+                    let parsed = if let Some(ms) = code.strip_prefix("wait=") {
+                        ms.parse::<f64>().ok().map(|ms| wait += ms.max(0.0))
+                    } else if let Some(value) = code.strip_prefix("color=") {
+                        // 属性値にそのまま書き込むので、色として読めないものは文字として表示する
+                        is_color(value).then(|| color = Some(value.to_string()))
+                    } else if code == "/color" {
+                        color = None;
+                        Some(())
+                    } else {
+                        None
+                    };
+                    if parsed.is_some() {
+                        rest = &rest[end + 1..];
+                        continue;
+                    }
+                }
+            }
+            glyphs.push(Glyph {
+                ch,
+                color: color.clone(),
+                wait,
+            });
+            wait = 0.0;
+            rest = &rest[ch.len_utf8()..];
+        }
+        TypewriterLine {
+            glyphs,
+            trailing_wait: wait,
+        }
+    }
+
+    // 次の行を出し始めるまでの時間
+    pub fn duration(&self, text_speed: f64) -> f64 {
+        self.glyphs
+            .iter()
+            .map(|glyph| wait(glyph.wait, text_speed) + text_speed)
+            .sum::<f64>()
+            + wait(self.trailing_wait, text_speed)
+    }
+
+    // 経過時間 elapsed で表示している文字数 (1 文字目は wait が無ければすぐ出す)
+    pub fn visible_count(&self, elapsed: f64, text_speed: f64) -> usize {
+        let mut appear_at = 0.0;
+        for (index, glyph) in self.glyphs.iter().enumerate() {
+            appear_at += wait(glyph.wait, text_speed);
+            if elapsed < appear_at {
+                return index;
+            }
+            appear_at += text_speed;
+        }
+        self.glyphs.len()
+    }
+
+    // 先頭から count 文字分のマークアップ (色の付いた文字は tspan で囲む)
+    pub fn render(&self, count: usize) -> String {
+        let mut markup = String::new();
+        let mut current_color: Option<&String> = None;
+        for glyph in self.glyphs.iter().take(count) {
+            if glyph.color.as_ref() != current_color {
+                if current_color.is_some() {
+                    markup.push_str("</tspan>");
+                }
+                if let Some(color) = &glyph.color {
+                    markup.push_str(&format!("<tspan fill=\"{}\">", color));
+                }
+                current_color = glyph.color.as_ref();
+            }
+            match glyph.ch {
+                '&' => markup.push_str("&amp;"),
+                '<' => markup.push_str("&lt;"),
+                '>' => markup.push_str("&gt;"),
+                ch => markup.push(ch),
+            }
+        }
+        if current_color.is_some() {
+            markup.push_str("</tspan>");
+        }
+        markup
+    }
+}
+
+// 一度に表示する設定 (text_speed が 0) では止めない
+fn wait(wait: f64, text_speed: f64) -> f64 {
+    if text_speed == 0.0 {
+        0.0
+    } else {
+        wait
+    }
+}
+
+fn is_color(value: &str) -> bool {
+    match value.strip_prefix('#') {
+        Some(hex) => matches!(hex.len(), 3 | 6) && hex.chars().all(|ch| ch.is_ascii_hexdigit()),
+        None => !value.is_empty() && value.chars().all(|ch| ch.is_ascii_alphabetic()),
+    }
+}
+
+// メッセージウィンドウに表示中のページ (行は上から順に出す)
+#[derive(Clone, Debug)]
+pub struct Typewriter {
+    lines: Vec<TypewriterLine>,
+    start_step: f64,
+    completed: bool,
+}
+
+impl Typewriter {
+    pub fn new(lines: &[String], start_step: f64) -> Typewriter {
+        Typewriter {
+            lines: lines
+                .iter()
+                .map(|line| TypewriterLine::parse(line))
+                .collect(),
+            start_step,
+            completed: false,
+        }
+    }
+
+    // 残りを一度に表示する (決定キーが押された時)
+    pub fn complete(&mut self) {
+        self.completed = true;
+    }
+
+    // 各行のマークアップと、すべて出し終えたかを返す
+    pub fn update(&mut self, step: f64, text_speed: f64) -> (Vec<String>, bool) {
+        let text_speed = text_speed.max(0.0);
+        let mut elapsed = if self.completed {
+            f64::INFINITY
+        } else {
+            step - self.start_step
+        };
+        let mut finished = true;
+        let mut markups = vec![];
+        for line in self.lines.iter() {
+            let duration = line.duration(text_speed);
+            markups.push(line.render(line.visible_count(elapsed, text_speed)));
+            finished = finished && elapsed >= duration;
+            // 前の行を出し終えるまで次の行は出さない (elapsed が負になる)
+            elapsed -= duration;
+        }
+        (markups, finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_color_codes_as_tspan() {
+        let line = TypewriterLine::parse("a[color=#7fff7f]b[/color]c");
+        assert_eq!(line.render(3), "a<tspan fill=\"#7fff7f\">b</tspan>c");
+        assert_eq!(line.render(2), "a<tspan fill=\"#7fff7f\">b</tspan>");
+    }
+
+    #[test]
+    fn shows_invalid_color_as_text() {
+        let line = TypewriterLine::parse("[color=\" onload=\"x]a");
+        assert_eq!(line.render(100), "[color=\" onload=\"x]a");
+        let line = TypewriterLine::parse("[color=#12345g]a");
+        assert_eq!(line.render(100), "[color=#12345g]a");
+    }
+
+    #[test]
+    fn waits_before_next_glyph() {
+        let line = TypewriterLine::parse("ab[wait=500]c[wait=100]");
+        assert_eq!(line.duration(30.0), 90.0 + 600.0);
+        assert_eq!(line.visible_count(0.0, 30.0), 1);
+        assert_eq!(line.visible_count(30.0, 30.0), 2);
+        assert_eq!(line.visible_count(559.0, 30.0), 2);
+        assert_eq!(line.visible_count(560.0, 30.0), 3);
+    }
+
+    #[test]
+    fn skips_waits_when_shown_at_once() {
+        let line = TypewriterLine::parse("ab[wait=500]c[wait=100]");
+        assert_eq!(line.duration(0.0), 0.0);
+        assert_eq!(line.visible_count(0.0, 0.0), 3);
+    }
+}
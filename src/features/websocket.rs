@@ -1,9 +1,10 @@
+use crate::engine::error::EngineError;
+use crate::utils::console_log;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::rc::Rc;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsCast;
-use wasm_bindgen_test::console_log;
 use web_sys::{MessageEvent, WebSocket};
 
 #[derive(Serialize, Debug)]
@@ -30,6 +31,8 @@ pub struct WebSocketWrapper {
     ws: WebSocket,
     pub state: Rc<RefCell<WebSocketState>>,
     pub messages: Rc<RefCell<Vec<ChannelMessage>>>,
+    // 受信したが解釈できなかったメッセージ
+    pub errors: Rc<RefCell<Vec<EngineError>>>,
     pub user_name: String,
 }
 
@@ -54,6 +57,7 @@ impl WebSocketWrapper {
                 has_connection_request: true,
             })),
             messages: Rc::new(RefCell::new(vec![])),
+            errors: Rc::new(RefCell::new(vec![])),
             user_name: user_name.to_owned(),
         };
         websocket_wrapper.set_callbacks();
@@ -74,22 +78,29 @@ impl WebSocketWrapper {
                 .send_with_str(&serde_json::to_string(&channel_user).unwrap())
                 .unwrap();
             let mut state_clone = state_clone.borrow_mut();
-            (*state_clone).is_opened = true;
-            (*state_clone).has_connection_request = false;
-            (*state_clone).is_closed = false;
-            (*state_clone).is_joined = false;
+            state_clone.is_opened = true;
+            state_clone.has_connection_request = false;
+            state_clone.is_closed = false;
+            state_clone.is_joined = false;
         });
         self.ws
             .set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
         onopen_callback.forget();
 
         let clone_messages = self.messages.clone();
+        let clone_errors = self.errors.clone();
         let onmessage_callback = Closure::<dyn FnMut(_)>::new(move |e: MessageEvent| {
             if let Ok(raw_text) = e.data().dyn_into::<js_sys::JsString>() {
                 let raw_text = raw_text.as_string().unwrap();
-                let received_message: ChannelMessage = serde_json::from_str(&raw_text).unwrap();
-                let mut clone_messages = clone_messages.borrow_mut();
-                (*clone_messages).push(received_message);
+                match serde_json::from_str::<ChannelMessage>(&raw_text) {
+                    Ok(received_message) => {
+                        let mut clone_messages = clone_messages.borrow_mut();
+                        (*clone_messages).push(received_message);
+                    }
+                    Err(e) => clone_errors
+                        .borrow_mut()
+                        .push(EngineError::InvalidChannelMessage(e.to_string())),
+                }
             }
         });
         self.ws
@@ -101,7 +112,7 @@ impl WebSocketWrapper {
             console_log!("websocket connection closed.");
 
             let mut state_clone = state_clone.borrow_mut();
-            (*state_clone).is_closed = true;
+            state_clone.is_closed = true;
         });
         self.ws
             .set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
@@ -4,11 +4,63 @@ mod rpg;
 mod svg;
 mod utils;
 
+use crate::engine::error::EngineError;
+use crate::engine::scene::SceneRegistry;
+use crate::engine::session::SessionLog;
+use crate::engine::state::State;
 use crate::engine::Engine;
-use features::animation::Animation;
+// 追加のシーンからもアニメーションを組み立てられるようにする
+pub use features::animation::{Animation, AnimationCallback, Cue};
+pub use features::timeline::{Timeline, TimelineHandle, Track};
+pub use features::tween::{Easing, Tween};
+use std::rc::Rc;
+pub use svg::backend::memory::{MemoryBackend, MemoryElement};
+pub use svg::backend::sandbox::SandboxBackend;
+pub use svg::backend::web::WebBackend;
+pub use svg::backend::{DomBackend, Element};
+pub use svg::element_wrapper::ElementWrapper;
 use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsError;
 
+// root の中に index.html と同じ構成の SVG を置いておく (要素の検索は root の中に限る)
+// seed を省略した場合はランダムに決まる (engine.seed() で取得できる、ロードするとセーブデータのシードに変わる)
 #[wasm_bindgen]
-pub fn create_rpg_engine() -> Engine {
-    rpg::mount()
+pub fn create_rpg_engine(root: web_sys::Element, seed: Option<u64>) -> Engine {
+    utils::set_panic_hook();
+    rpg::mount(Rc::new(WebBackend::with_root(root)), true, seed)
+}
+
+// ブラウザ外で動かすためのエンジン (WebSocket には接続しない)
+pub fn create_headless_rpg_engine(document: Rc<dyn DomBackend>, seed: u64) -> Engine {
+    rpg::mount(document, false, Some(seed))
+}
+
+// ゲーム側で実装した Scene を組み込みのシーンに加えて登録する
+// 登録のエラーはホストに通知され、登録できたシーンだけで動く
+pub fn create_rpg_engine_with_scenes(
+    document: Rc<dyn DomBackend>,
+    online: bool,
+    seed: Option<u64>,
+    register_scenes: impl Fn(&mut SceneRegistry, &mut State) -> Result<(), EngineError> + 'static,
+) -> Engine {
+    rpg::mount_with_scenes(document, online, seed, register_scenes)
+}
+
+// export_session_log() で書き出したログを新しいエンジンで再生する (WebSocket には接続しない)
+// 保存先は切り離され、ログに記録されたセーブデータだけが置かれる (ページのセーブデータは読み書きしない)
+// ログが壊れている場合や、記録されたセーブデータで再生できない場合は JS 側に例外として返す
+#[wasm_bindgen]
+pub fn replay_rpg_engine(root: web_sys::Element, session_log: String) -> Result<Engine, JsError> {
+    let session_log = SessionLog::from_json(&session_log)?;
+    Ok(rpg::replay(
+        Rc::new(WebBackend::with_root(root)),
+        &session_log,
+    )?)
+}
+
+pub fn replay_headless_rpg_engine(
+    document: Rc<dyn DomBackend>,
+    session_log: &SessionLog,
+) -> Result<Engine, EngineError> {
+    rpg::replay(document, session_log)
 }
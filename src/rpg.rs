@@ -1,8 +1,19 @@
 use crate::engine::application_types::StateType;
+use crate::engine::catalog::Catalog;
+use crate::engine::error::EngineError;
+use crate::engine::key_bindings::KeyBindings;
+use crate::engine::scene::{RegisterScenes, SceneRegistry};
+use crate::engine::session::SessionLog;
 use crate::engine::state::{Primitives, References, State};
 use crate::engine::Engine;
 use crate::features::animation::Animation;
+use crate::features::camera::Camera;
+use crate::features::timeline::Timeline;
+use crate::features::typewriter::DEFAULT_TEXT_SPEED;
+#[cfg(feature = "online")]
 use crate::features::websocket::WebSocketWrapper;
+use crate::svg::backend::sandbox::SandboxBackend;
+use crate::svg::backend::DomBackend;
 use crate::svg::Position;
 use crate::svg::SharedElements;
 use mechanism::item::Item;
@@ -12,12 +23,17 @@ use scenes::event::EventState;
 use scenes::field::FieldState;
 use scenes::menu::MenuState;
 use scenes::title::TitleState;
+use scenes::{BATTLE_SCENE, EVENT_SCENE, FIELD_SCENE, MENU_SCENE, TITLE_SCENE};
 use serde::{Deserialize, Serialize};
 use state::character::Character;
 use state::rpg_shared_state::RPGSharedState;
+use state::snapshot::RPGSnapshot;
+use state::variables::Variables;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+#[cfg(feature = "dev-console")]
+pub mod dev_console;
 pub mod mechanism;
 pub mod scenes;
 pub mod state;
@@ -30,17 +46,33 @@ pub struct SaveData {
     map_i32: Vec<i32>,
     inventory_string: Vec<String>,
     check_token: u32,
+    // 古いセーブデータの位置で管理していたフラグ (読み込み時に variables へ移し、書き出さない)
+    #[serde(default, skip_serializing)]
     event_flags: Vec<bool>,
+    // 古いセーブデータには存在しない (rng_word_pos と合わせて、呼び出し側が update の前に入れておく)
+    #[serde(default)]
+    pub rng_seed: Option<u64>,
+    // rng_seed から引いた数 (セーブ時に引き直していた頃のデータには存在せず、0 から続ける)
+    #[serde(default)]
+    pub rng_word_pos: Option<u128>,
+    #[serde(default)]
+    pub key_bindings: Option<KeyBindings>,
+    // 呼び出し側が update の前に入れておく
+    #[serde(default)]
+    pub variables: Option<Variables>,
 }
 
 impl SaveData {
-    pub fn load(&mut self, characters: &mut Vec<Character>, try_get_storage: bool) {
+    pub fn load(
+        &mut self,
+        document: &Rc<dyn DomBackend>,
+        characters: &mut [Character],
+        variables: &mut Variables,
+        try_get_storage: bool,
+    ) -> Result<(), EngineError> {
         if try_get_storage {
-            let storage = web_sys::window().unwrap().local_storage().unwrap().unwrap();
-            let raw_save = storage.get_item("save").unwrap();
-            if raw_save.is_some() {
-                let raw_save = raw_save.unwrap();
-                let local_save_data: SaveData = serde_json::from_str(raw_save.as_str()).unwrap();
+            if let Some(raw_save) = document.get_storage_item("save") {
+                let local_save_data = SaveData::parse(&raw_save)?;
                 self.character_u32 = local_save_data.character_u32.to_vec();
                 self.treasure_box_usize = local_save_data.treasure_box_usize.to_vec();
                 self.map_usize = local_save_data.map_usize.to_vec();
@@ -48,24 +80,54 @@ impl SaveData {
                 self.inventory_string = local_save_data.inventory_string.to_vec();
                 self.event_flags = local_save_data.event_flags.to_vec();
                 self.check_token = local_save_data.check_token.to_owned();
+                self.rng_seed = local_save_data.rng_seed;
+                self.rng_word_pos = local_save_data.rng_word_pos;
+                self.key_bindings = local_save_data.key_bindings;
+                self.variables = local_save_data.variables;
             }
         }
-        characters[0].current_hp = *self.character_u32.get(0).unwrap();
-        characters[0].max_hp = *self.character_u32.get(1).unwrap();
-        characters[0].position.x = *self.map_i32.get(0).unwrap();
-        characters[0].position.y = *self.map_i32.get(1).unwrap();
-        characters[0].inventory = self
+        // キャラクターに反映する前に、すべての値が揃っているか確認する
+        let (current_hp, max_hp) = match self.character_u32[..] {
+            [current_hp, max_hp, ..] => (current_hp, max_hp),
+            _ => return Err(EngineError::CorruptedSave("character_u32".to_string())),
+        };
+        let (x, y) = match self.map_i32[..] {
+            [x, y, ..] => (x, y),
+            _ => return Err(EngineError::CorruptedSave("map_i32".to_string())),
+        };
+        self.map_index()?;
+        let inventory = self
             .inventory_string
             .iter()
             .map(|s| Item::new(s.as_str()))
-            .collect();
-        characters[0].event_flags = self.event_flags.to_vec();
+            .collect::<Result<Vec<Item>, EngineError>>()?;
+        characters[0].current_hp = current_hp;
+        characters[0].max_hp = max_hp;
+        characters[0].position.x = x;
+        characters[0].position.y = y;
+        characters[0].inventory = inventory;
+        *variables = match &self.variables {
+            Some(variables) => variables.clone(),
+            None => Variables::from_legacy_flags(&self.event_flags),
+        };
+        Ok(())
+    }
+    pub fn map_index(&self) -> Result<usize, EngineError> {
+        self.map_usize
+            .first()
+            .copied()
+            .ok_or_else(|| EngineError::CorruptedSave("map_usize".to_string()))
+    }
+    fn parse(raw_save: &str) -> Result<SaveData, EngineError> {
+        serde_json::from_str(raw_save).map_err(|e| EngineError::CorruptedSave(e.to_string()))
     }
     pub fn update(
         &mut self,
-        characters: &Vec<Character>,
-        treasure_box_opened: &Vec<Vec<usize>>,
+        document: &Rc<dyn DomBackend>,
+        characters: &[Character],
+        treasure_box_opened: &[Vec<usize>],
         map_index: usize,
+        key_bindings: &KeyBindings,
     ) {
         self.character_u32 = vec![characters[0].current_hp, characters[0].max_hp];
         self.treasure_box_usize = treasure_box_opened.to_vec();
@@ -76,27 +138,17 @@ impl SaveData {
             .iter()
             .map(|item| item.name.clone())
             .collect::<Vec<String>>();
-        self.event_flags = characters[0].event_flags.to_vec();
-        let storage = web_sys::window().unwrap().local_storage().unwrap().unwrap();
+        self.key_bindings = Some(key_bindings.clone());
         let json = serde_json::to_string(self).unwrap();
-        storage.set_item("save", json.as_str()).unwrap();
+        document.set_storage_item("save", json.as_str());
     }
-    fn new(
-        character_u32: Vec<u32>,
-        treasure_box_usize: Vec<Vec<usize>>,
-        map_usize: Vec<usize>,
-        map_i32: Vec<i32>,
-        inventory_string: Vec<String>,
-        event_flags: Vec<bool>,
-    ) -> SaveData {
-        SaveData {
-            character_u32,
-            treasure_box_usize,
-            map_usize,
-            map_i32,
-            inventory_string,
-            event_flags,
-            check_token: 0,
+    // タイトル画面から使えるよう、ロード前にキー割り当てだけ読み出す
+    pub fn load_key_bindings(
+        document: &Rc<dyn DomBackend>,
+    ) -> Result<Option<KeyBindings>, EngineError> {
+        match document.get_storage_item("save") {
+            Some(raw_save) => Ok(SaveData::parse(&raw_save)?.key_bindings),
+            None => Ok(None),
         }
     }
     pub fn empty() -> SaveData {
@@ -108,17 +160,75 @@ impl SaveData {
             inventory_string: vec![],
             event_flags: vec![],
             check_token: 0,
+            rng_seed: None,
+            rng_word_pos: None,
+            key_bindings: None,
+            variables: None,
         }
     }
 }
 
-pub fn mount() -> Engine {
-    let mut rng = rand::thread_rng();
+pub fn mount(document: Rc<dyn DomBackend>, online: bool, seed: Option<u64>) -> Engine {
+    mount_with_scenes(document, online, seed, |_, _| Ok(()))
+}
+
+// 記録開始時のセーブデータ (無ければ空) だけを置いた保存先でマウントし、ログを再生する
+pub fn replay(
+    document: Rc<dyn DomBackend>,
+    session_log: &SessionLog,
+) -> Result<Engine, EngineError> {
+    let sandbox = SandboxBackend::new(document);
+    if let Some(initial_save) = &session_log.initial_save {
+        sandbox.set_storage_item("save", initial_save);
+    }
+    let mut engine = mount(Rc::new(sandbox), false, Some(session_log.seed));
+    engine.replay(session_log)?;
+    Ok(engine)
+}
+
+// 組み込みのシーンを登録した後に register_scenes で追加のシーンを登録できる
+// register_scenes は巻き戻しでエンジンを作り直す時にも呼ばれる
+pub fn mount_with_scenes(
+    document: Rc<dyn DomBackend>,
+    online: bool,
+    seed: Option<u64>,
+    register_scenes: impl Fn(&mut SceneRegistry, &mut State) -> Result<(), EngineError> + 'static,
+) -> Engine {
+    mount_engine(document, online, seed, Rc::new(register_scenes))
+}
+
+fn register_builtin_scenes(
+    scenes: &mut SceneRegistry,
+    shared_state: &mut State,
+) -> Result<(), EngineError> {
+    scenes
+        .register(TITLE_SCENE, TitleState::create_title_scene(shared_state))?
+        .register(EVENT_SCENE, EventState::create_event_scene(shared_state))?
+        .register(FIELD_SCENE, FieldState::create_field_scene(shared_state)?)?
+        .register(BATTLE_SCENE, BattleState::create_battle_scene(shared_state))?
+        .register(MENU_SCENE, MenuState::create_menu_scene(shared_state))?;
+    Ok(())
+}
+
+fn mount_engine(
+    document: Rc<dyn DomBackend>,
+    online: bool,
+    seed: Option<u64>,
+    register_scenes: RegisterScenes,
+) -> Engine {
+    let (seed, mut rng) = State::create_rng(seed);
     let random_number = rng.random::<u16>();
     let user_name = random_number.to_string();
+    // フィールドの要素が無い描画先でも動かせるよう、繋がっていない要素に書き込むカメラで代える
+    let (camera, camera_error) = match Camera::new(&document) {
+        Ok(camera) => (camera, None),
+        Err(error) => (Camera::detached(&document), Some(error)),
+    };
+    let (elements, element_errors) = SharedElements::new(document.clone());
     let rpg_shared_state = RPGSharedState {
         treasure_box_opened: vec![],
         save_data: SaveData::empty(),
+        #[cfg(feature = "online")]
         online_users: vec![],
         to_send_channel_messages: vec![],
         characters: vec![Character {
@@ -126,35 +236,76 @@ pub fn mount() -> Engine {
             max_hp: 80,
             position: Position { x: -1, y: -1 },
             inventory: vec![],
-            event_flags: vec![],
         }],
+        variables: Variables::new(),
+        requested_event: None,
+        camera,
     };
+    // 既定のロケールは ja (切り替えは Engine::set_locale から)
+    let catalog = Catalog::from_json(include_str!("rpg/messages.json"), "ja").unwrap();
+    catalog.apply(&document);
     let mut shared_state = State {
         user_name: user_name.to_owned(),
         to_send_channel_messages: vec![],
-        elements: SharedElements::new(),
-        interrupt_animations: vec![vec![Animation::always_blink()]],
+        emitted_events: vec![],
+        errors: element_errors,
+        elements,
+        interrupt_animations: Timeline::new(),
         state_type: StateType::RPGShared(rpg_shared_state),
         primitives: Primitives {
-            scene_index: 0,
-            requested_scene_index: 0,
+            scene_stack: vec![TITLE_SCENE.to_string()],
+            requested_scene_transition: None,
             map_index: 0,
             requested_map_index: 0,
         },
         references: Rc::new(RefCell::new(References {
             has_block_message: false,
             has_continuous_message: false,
+            revealing_message: false,
+            complete_message_requested: false,
+            text_speed: DEFAULT_TEXT_SPEED,
         })),
+        rng,
+        seed,
+        key_bindings: KeyBindings::new(),
+        held_inputs: vec![],
+        catalog: Rc::new(RefCell::new(catalog)),
     };
-    let mut scenes = vec![
-        TitleState::create_title_scene(&mut shared_state),
-        EventState::create_event_scene(&mut shared_state),
-        FieldState::create_field_scene(&mut shared_state),
-        BattleState::create_battle_scene(&mut shared_state),
-        MenuState::create_menu_scene(&mut shared_state),
-    ];
-    let init_func = scenes[0].init_func;
-    init_func(&mut scenes[0], &mut shared_state);
-    let web_socket_wrapper = WebSocketWrapper::new(shared_state.user_name.to_owned());
-    Engine::new(shared_state, scenes, web_socket_wrapper)
+    if let Some(error) = camera_error {
+        shared_state.report_error(error);
+    }
+    match SaveData::load_key_bindings(&shared_state.elements.document) {
+        Ok(Some(key_bindings)) => shared_state.key_bindings = key_bindings,
+        Ok(None) => {}
+        Err(error) => shared_state.report_error(error),
+    }
+    shared_state
+        .interrupt_animations
+        .push(vec![Animation::always_blink(&shared_state.elements)]);
+    let mut scenes = SceneRegistry::new();
+    // 登録できなかったシーンはホストに通知し、残りのシーンで動かす
+    let registered = register_builtin_scenes(&mut scenes, &mut shared_state)
+        .and_then(|()| register_scenes(&mut scenes, &mut shared_state));
+    if let Err(error) = registered {
+        shared_state.report_error(error);
+    }
+    match scenes.get_mut(TITLE_SCENE) {
+        Ok(scene) => scene.init(&mut shared_state),
+        Err(error) => shared_state.report_error(error),
+    }
+    let mut engine = Engine::new(shared_state, scenes);
+    // online フィーチャーを外したビルドでは接続しない
+    if online {
+        #[cfg(feature = "online")]
+        {
+            let user_name = engine.shared_state.user_name.to_owned();
+            engine.web_socket_wrapper = Some(WebSocketWrapper::new(user_name));
+        }
+    }
+    engine.inspect_game = Some(Box::new(RPGSnapshot::inspect));
+    let seed = engine.seed();
+    engine.time_travel.remount = Some(Rc::new(move |document| {
+        mount_engine(document, false, Some(seed), register_scenes.clone())
+    }));
+    engine
 }
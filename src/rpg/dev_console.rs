@@ -0,0 +1,257 @@
+use crate::engine::application_types::StateType;
+use crate::engine::error::EngineError;
+use crate::engine::event_bus::GameEvent;
+use crate::engine::state::State;
+use crate::rpg::mechanism::item::Item;
+use crate::rpg::scenes::field::{FieldState, MAP_COUNT};
+use crate::rpg::scenes::{BATTLE_SCENE, EVENT_SCENE, FIELD_SCENE};
+use crate::svg::Position;
+
+// テスト用のコマンド (dev-console フィーチャーでのみ使う)
+//   teleport <map_index> <x> <y>
+//   give <item_name>
+//   flag <name> [on|off]   値を省略すると反転
+//   var <name> <value>
+//   battle
+//   event <event_id>   events.json のスクリプトを実行する
+//   camera zoom <scale> | camera shake
+pub enum DevCommand {
+    Teleport {
+        map_index: usize,
+        position: Position,
+    },
+    Give(String),
+    Flag {
+        name: String,
+        value: Option<bool>,
+    },
+    Var {
+        name: String,
+        value: i64,
+    },
+    Battle,
+    Event(String),
+    CameraZoom(f64),
+    CameraShake,
+}
+
+impl DevCommand {
+    pub fn parse(command: &str) -> Result<DevCommand, EngineError> {
+        let invalid = || EngineError::InvalidCommand(command.to_string());
+        let words = command.split_whitespace().collect::<Vec<&str>>();
+        match words[..] {
+            ["teleport", map_index, x, y] => {
+                let map_index = map_index.parse::<usize>().map_err(|_| invalid())?;
+                let x = x.parse::<i32>().map_err(|_| invalid())?;
+                let y = y.parse::<i32>().map_err(|_| invalid())?;
+                // マップは 40px のマス目 (地面は負の座標にも広がる)
+                let position = Position::new(x, y);
+                if map_index >= MAP_COUNT
+                    || x % 40 != 0
+                    || y % 40 != 0
+                    || !FieldState::is_walkable(map_index, position)?
+                {
+                    return Err(invalid());
+                }
+                Ok(DevCommand::Teleport {
+                    map_index,
+                    position,
+                })
+            }
+            ["give", ..] if words.len() > 1 => {
+                let item_name = words[1..].join(" ");
+                Item::new(&item_name)?;
+                Ok(DevCommand::Give(item_name))
+            }
+            ["flag", name] | ["flag", name, _] => {
+                let value = match words.get(2) {
+                    None => None,
+                    Some(&"on") => Some(true),
+                    Some(&"off") => Some(false),
+                    Some(_) => return Err(invalid()),
+                };
+                Ok(DevCommand::Flag {
+                    name: name.to_string(),
+                    value,
+                })
+            }
+            ["var", name, value] => {
+                let value = value.parse::<i64>().map_err(|_| invalid())?;
+                Ok(DevCommand::Var {
+                    name: name.to_string(),
+                    value,
+                })
+            }
+            ["battle"] => Ok(DevCommand::Battle),
+            ["event", event_id] => Ok(DevCommand::Event(event_id.to_string())),
+            ["camera", "zoom", scale] => match scale.parse::<f64>() {
+                Ok(scale) if scale > 0.0 => Ok(DevCommand::CameraZoom(scale)),
+                _ => Err(invalid()),
+            },
+            ["camera", "shake"] => Ok(DevCommand::CameraShake),
+            _ => Err(invalid()),
+        }
+    }
+
+    // 実行結果をコンソールに表示する文字列で返す
+    pub fn apply(self, shared_state: &mut State) -> Result<String, EngineError> {
+        // タイトル画面ではまだゲームの状態が用意されていない
+        if shared_state.primitives.scene_id() != FIELD_SCENE {
+            return Err(EngineError::InvalidCommand(format!(
+                "commands are only available in the `{}` scene",
+                FIELD_SCENE
+            )));
+        }
+        if let State {
+            state_type: StateType::RPGShared(rpg_shared_state),
+            primitives,
+            emitted_events,
+            elements,
+            interrupt_animations,
+            ..
+        } = shared_state
+        {
+            let character = &mut rpg_shared_state.characters[0];
+            match self {
+                DevCommand::Teleport {
+                    map_index,
+                    position,
+                } => {
+                    let output = format!(
+                        "teleported to map {} ({}, {})",
+                        map_index, position.x, position.y
+                    );
+                    character.position = position;
+                    primitives.requested_map_index = map_index;
+                    Ok(output)
+                }
+                DevCommand::Give(item_name) => {
+                    character.inventory.push(Item::new(&item_name)?);
+                    Ok(format!("gave {}", item_name))
+                }
+                DevCommand::Flag { name, value } => {
+                    let variables = &mut rpg_shared_state.variables;
+                    let value = value.unwrap_or(!variables.flag(&name));
+                    variables.set_flag(&name, value);
+                    Ok(format!("{} = {}", name, value))
+                }
+                DevCommand::Var { name, value } => {
+                    rpg_shared_state.variables.set_int(&name, value);
+                    Ok(format!("{} = {}", name, value))
+                }
+                DevCommand::Battle => {
+                    primitives.request_scene(BATTLE_SCENE);
+                    emitted_events.push(GameEvent::BattleStarted {
+                        map_index: primitives.map_index,
+                    });
+                    Ok("battle started".to_string())
+                }
+                DevCommand::Event(event_id) => {
+                    let output = format!("event {} started", event_id);
+                    rpg_shared_state.requested_event = Some(event_id);
+                    primitives.request_scene(EVENT_SCENE);
+                    Ok(output)
+                }
+                DevCommand::CameraZoom(scale) => {
+                    rpg_shared_state.camera.set_zoom(scale);
+                    Ok(format!("camera zoom = {}", scale))
+                }
+                DevCommand::CameraShake => {
+                    interrupt_animations
+                        .push(vec![rpg_shared_state.camera.shake(elements, 8, 400)]);
+                    Ok("camera shaken".to_string())
+                }
+            }
+        } else {
+            Err(EngineError::UnsupportedStateType(
+                "dev commands need the RPG state".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn teleport(command: &str) -> Option<(usize, i32, i32)> {
+        match DevCommand::parse(command) {
+            Ok(DevCommand::Teleport {
+                map_index,
+                position,
+            }) => Some((map_index, position.x, position.y)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn teleport_accepts_ground_tiles_of_every_map() {
+        assert_eq!(teleport("teleport 0 360 280"), Some((0, 360, 280)));
+        // 洞窟のマップは負の座標に置かれている
+        assert_eq!(teleport("teleport 1 -440 -360"), Some((1, -440, -360)));
+        assert_eq!(teleport("teleport 2 -440 -800"), Some((2, -440, -800)));
+        assert_eq!(teleport("teleport 3  -440   -400"), Some((3, -440, -400)));
+    }
+
+    #[test]
+    fn teleport_rejects_tiles_out_of_range() {
+        // マップが無い・地面の外・岩の上
+        assert_eq!(teleport("teleport 4 0 0"), None);
+        assert_eq!(teleport("teleport 1 0 0"), None);
+        assert_eq!(teleport("teleport 1 -600 -600"), None);
+        assert_eq!(teleport("teleport 0 80 -80"), None);
+        assert_eq!(teleport("teleport 0 4000 0"), None);
+    }
+
+    #[test]
+    fn malformed_commands_are_rejected() {
+        for command in [
+            "",
+            "teleport",
+            "teleport 0 360",
+            "teleport 0 360 280 1",
+            "teleport -1 360 280",
+            "teleport 0 x 280",
+            "teleport 0 370 280",
+            "give",
+            "give 伝説の剣",
+            "flag opening_done maybe",
+            "var elder_visits many",
+            "camera zoom 0",
+            "camera pan 1",
+            "dance",
+        ] {
+            assert!(
+                matches!(
+                    DevCommand::parse(command),
+                    Err(EngineError::InvalidCommand(_)) | Err(EngineError::UnknownItem(_))
+                ),
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn other_commands_are_parsed() {
+        assert!(matches!(
+            DevCommand::parse("give 薬草"),
+            Ok(DevCommand::Give(name)) if name == "薬草"
+        ));
+        assert!(matches!(
+            DevCommand::parse("flag elder_met off"),
+            Ok(DevCommand::Flag {
+                value: Some(false),
+                ..
+            })
+        ));
+        assert!(matches!(
+            DevCommand::parse("var elder_visits -3"),
+            Ok(DevCommand::Var { value: -3, .. })
+        ));
+        assert!(matches!(
+            DevCommand::parse("camera zoom 1.5"),
+            Ok(DevCommand::CameraZoom(scale)) if scale == 1.5
+        ));
+    }
+}
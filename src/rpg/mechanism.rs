@@ -1,3 +1,4 @@
 pub mod choice_kind;
 pub mod choice_setting;
+pub mod event_script;
 pub mod item;
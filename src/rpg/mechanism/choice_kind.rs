@@ -29,26 +29,27 @@ pub enum ChoiceKind {
 }
 
 impl ChoiceKind {
+    // 表示する文言の id (描画時に Catalog で引く)
     pub fn get_choice_string(&self) -> String {
         match self {
             Root => "",
             Menu => "",
-            UseItem => "つかう",
-            DropItem => "すてる",
-            Yes => "はい",
-            No => "いいえ",
-            Battle => "たたかう",
-            Escape => "にげる",
-            Special => "とくぎ",
-            ItemInventory => "どうぐ",
-            Spell => "じゅもん",
-            Equip => "そうび",
-            Save => "セーブ",
-            Title => "タイトル",
-            CloseMenu => "とじる",
-            Emote => "エモート",
+            UseItem => "choice.use_item",
+            DropItem => "choice.drop_item",
+            Yes => "choice.yes",
+            No => "choice.no",
+            Battle => "choice.battle",
+            Escape => "choice.escape",
+            Special => "choice.special",
+            ItemInventory => "choice.item_inventory",
+            Spell => "choice.spell",
+            Equip => "choice.equip",
+            Save => "choice.save",
+            Title => "choice.title",
+            CloseMenu => "choice.close_menu",
+            Emote => "choice.emote",
             SendEmote => "",
-            Chat => "チャット",
+            Chat => "choice.chat",
             Confirm => "",
             Undo => "",
             Decide => "",
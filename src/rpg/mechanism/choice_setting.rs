@@ -54,11 +54,10 @@ impl ChoiceSetting {
         let drop_choice = Choice {
             own_token: DropItem,
             label: DropItem.get_choice_string(),
-            branch_description: Some("本当に捨てますか？".to_string()),
+            branch_description: Some("confirm.drop_item".to_string()),
             branch: Some(vec![Choice::confirm_choice()]),
         };
-        let mut setting = ChoiceSetting::new();
-        setting.add_choices(&mut vec![
+        let mut choices = vec![
             Choice {
                 own_token: ItemInventory,
                 label: "".to_string(),
@@ -76,32 +75,39 @@ impl ChoiceSetting {
                 }]),
             },
             Choice::no_choice_from(Equip),
-            Choice {
-                own_token: Emote,
+        ];
+        // 通信やエモートを含まないビルドでは項目ごと表示しない
+        #[cfg(feature = "emote")]
+        choices.push(Choice {
+            own_token: Emote,
+            label: "".to_string(),
+            branch_description: None,
+            branch: Some(vec![Choice {
+                own_token: ChoseNth("Emote".to_string(), None),
                 label: "".to_string(),
                 branch_description: None,
-                branch: Some(vec![Choice {
-                    own_token: ChoseNth("Emote".to_string(), None),
-                    label: "".to_string(),
-                    branch_description: None,
-                    branch: Some(vec![Choice::no_choice_from(SendEmote)]),
-                }]),
-            },
-            Choice::no_choice_from(Chat),
+                branch: Some(vec![Choice::no_choice_from(SendEmote)]),
+            }]),
+        });
+        #[cfg(feature = "online")]
+        choices.push(Choice::no_choice_from(Chat));
+        choices.append(&mut vec![
             Choice {
                 own_token: Save,
                 label: "".to_string(),
-                branch_description: Some("セーブを上書きします。よろしいですか？".to_string()),
+                branch_description: Some("confirm.save".to_string()),
                 branch: Some(vec![Choice::confirm_choice()]),
             },
             Choice {
                 own_token: Title,
                 label: "".to_string(),
-                branch_description: Some("タイトルに戻ります。よろしいですか？".to_string()),
+                branch_description: Some("confirm.title".to_string()),
                 branch: Some(vec![Choice::confirm_choice()]),
             },
             Choice::no_choice_from(CloseMenu),
         ]);
+        let mut setting = ChoiceSetting::new();
+        setting.add_choices(&mut choices);
         setting
     }
 }
@@ -0,0 +1,276 @@
+use crate::engine::application_types::StateType;
+use crate::engine::error::EngineError;
+use crate::engine::event_bus::GameEvent;
+use crate::engine::state::State;
+use crate::features::animation::Animation;
+use crate::rpg::mechanism::item::Item;
+use crate::rpg::scenes::field::MAP_COUNT;
+use crate::rpg::scenes::BATTLE_SCENE;
+use crate::svg::Position;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// イベントのスクリプト (events.json) の命令
+// {"イベントの id": [{"command": "message", "message": "event.opening"}, ...]} の形で書く
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum EventCommand {
+    // message は Catalog の id (改行で区切った行を 2 行ずつ表示する)
+    Message {
+        message: String,
+    },
+    IfFlag {
+        flag: String,
+        #[serde(default)]
+        then: Vec<EventCommand>,
+        #[serde(default, rename = "else")]
+        otherwise: Vec<EventCommand>,
+    },
+    SetFlag {
+        flag: String,
+        #[serde(default = "default_flag_value")]
+        value: bool,
+    },
+    // 変数 var と value を op で比べる
+    IfVar {
+        var: String,
+        op: Comparison,
+        value: i64,
+        #[serde(default)]
+        then: Vec<EventCommand>,
+        #[serde(default, rename = "else")]
+        otherwise: Vec<EventCommand>,
+    },
+    SetVar {
+        var: String,
+        value: i64,
+    },
+    AddVar {
+        var: String,
+        amount: i64,
+    },
+    GiveItem {
+        item: String,
+    },
+    // 持っていなければ何もしない
+    RemoveItem {
+        item: String,
+    },
+    // x, y はマップ上の座標 (40px のマス目)
+    ChangeMap {
+        map_index: usize,
+        x: i32,
+        y: i32,
+    },
+    ChangeScene {
+        scene: String,
+    },
+    StartBattle,
+}
+
+fn default_flag_value() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    pub fn test(&self, left: i64, right: i64) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+}
+
+pub struct EventScripts {
+    scripts: HashMap<String, Vec<EventCommand>>,
+}
+
+impl EventScripts {
+    // 書き間違い (存在しないアイテム・マップ・シーン) のあるイベントはホストに通知して取り除く
+    // scene_ids はスクリプトから切り替えてよいシーン (イベントのシーンの next_scene_ids)
+    pub fn from_json(json: &str, scene_ids: &[&str], shared_state: &mut State) -> EventScripts {
+        let scripts: HashMap<String, Vec<EventCommand>> = match serde_json::from_str(json) {
+            Ok(scripts) => scripts,
+            Err(error) => {
+                shared_state.report_error(EngineError::InvalidEventScript(error.to_string()));
+                HashMap::new()
+            }
+        };
+        let mut valid_scripts = HashMap::new();
+        for (event_id, commands) in scripts {
+            match EventScripts::validate(&event_id, &commands, scene_ids) {
+                Ok(()) => {
+                    valid_scripts.insert(event_id, commands);
+                }
+                Err(error) => shared_state.report_error(error),
+            }
+        }
+        EventScripts {
+            scripts: valid_scripts,
+        }
+    }
+
+    fn validate(
+        event_id: &str,
+        commands: &[EventCommand],
+        scene_ids: &[&str],
+    ) -> Result<(), EngineError> {
+        let invalid = |reason: String| {
+            EngineError::InvalidEventScript(format!("event `{}`: {}", event_id, reason))
+        };
+        for command in commands {
+            match command {
+                EventCommand::IfFlag {
+                    then, otherwise, ..
+                }
+                | EventCommand::IfVar {
+                    then, otherwise, ..
+                } => {
+                    EventScripts::validate(event_id, then, scene_ids)?;
+                    EventScripts::validate(event_id, otherwise, scene_ids)?;
+                }
+                EventCommand::GiveItem { item } | EventCommand::RemoveItem { item } => {
+                    Item::new(item).map_err(|error| invalid(error.to_string()))?;
+                }
+                EventCommand::ChangeMap { map_index, .. } if *map_index >= MAP_COUNT => {
+                    return Err(invalid(format!("unknown map `{}`", map_index)));
+                }
+                EventCommand::ChangeScene { scene } if !scene_ids.contains(&scene.as_str()) => {
+                    return Err(invalid(format!("cannot change to scene `{}`", scene)));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, event_id: &str) -> Result<&Vec<EventCommand>, EngineError> {
+        self.scripts
+            .get(event_id)
+            .ok_or_else(|| EngineError::UnknownEvent(event_id.to_string()))
+    }
+
+    // 状態の変更はその場で反映し、メッセージは messages に積んで順に表示させる
+    // シーン遷移・マップ移動は primitives に要求するだけで、反映させる時点は呼び出し側 (EventState) が決める
+    pub fn run(
+        commands: &[EventCommand],
+        shared_state: &mut State,
+        messages: &mut Vec<Animation>,
+    ) -> Result<(), EngineError> {
+        for command in commands {
+            EventScripts::run_command(command, shared_state, messages)?;
+        }
+        Ok(())
+    }
+
+    fn run_command(
+        command: &EventCommand,
+        shared_state: &mut State,
+        messages: &mut Vec<Animation>,
+    ) -> Result<(), EngineError> {
+        if let State {
+            state_type: StateType::RPGShared(rpg_shared_state),
+            primitives,
+            emitted_events,
+            elements,
+            catalog,
+            ..
+        } = shared_state
+        {
+            let variables = &mut rpg_shared_state.variables;
+            let character = &mut rpg_shared_state.characters[0];
+            match command {
+                EventCommand::Message { message } => {
+                    messages.push(Animation::create_multi_line_messages(
+                        elements,
+                        catalog.borrow().lines(message),
+                    ));
+                }
+                EventCommand::IfFlag {
+                    flag,
+                    then,
+                    otherwise,
+                } => {
+                    let commands = if variables.flag(flag) {
+                        then
+                    } else {
+                        otherwise
+                    };
+                    EventScripts::run(commands, shared_state, messages)?;
+                }
+                EventCommand::SetFlag { flag, value } => {
+                    variables.set_flag(flag, *value);
+                }
+                EventCommand::IfVar {
+                    var,
+                    op,
+                    value,
+                    then,
+                    otherwise,
+                } => {
+                    let commands = if op.test(variables.int(var), *value) {
+                        then
+                    } else {
+                        otherwise
+                    };
+                    EventScripts::run(commands, shared_state, messages)?;
+                }
+                EventCommand::SetVar { var, value } => {
+                    variables.set_int(var, *value);
+                }
+                EventCommand::AddVar { var, amount } => {
+                    variables.add_int(var, *amount);
+                }
+                EventCommand::GiveItem { item } => {
+                    character.inventory.push(Item::new(item)?);
+                    emitted_events.push(GameEvent::ItemAcquired {
+                        item_name: item.to_owned(),
+                        map_index: primitives.map_index,
+                    });
+                }
+                EventCommand::RemoveItem { item } => {
+                    if let Some(index) = character
+                        .inventory
+                        .iter()
+                        .position(|held_item| held_item.name == *item)
+                    {
+                        character.inventory.remove(index);
+                    }
+                }
+                EventCommand::ChangeMap { map_index, x, y } => {
+                    character.position = Position::new(*x, *y);
+                    primitives.requested_map_index = *map_index;
+                }
+                EventCommand::ChangeScene { scene } => {
+                    primitives.request_scene(scene);
+                }
+                EventCommand::StartBattle => {
+                    primitives.request_scene(BATTLE_SCENE);
+                    emitted_events.push(GameEvent::BattleStarted {
+                        map_index: primitives.map_index,
+                    });
+                }
+            }
+            Ok(())
+        } else {
+            Err(EngineError::UnsupportedStateType(
+                "event scripts need the RPG state".to_string(),
+            ))
+        }
+    }
+}
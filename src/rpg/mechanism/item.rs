@@ -1,52 +1,54 @@
+use crate::engine::catalog::Catalog;
+use crate::engine::error::EngineError;
 use crate::rpg::RPGSharedState;
 
+// name はセーブデータやマップの定義で使う識別子 (表示名は Catalog で引く)
+#[derive(Clone)]
 pub struct Item {
     pub name: String,
     pub item_type: ItemType,
     pub consume_func: fn(&Item, &mut RPGSharedState),
-    pub description: String,
+    message_id: &'static str,
 }
 
 impl Item {
-    pub fn new(name: &str) -> Item {
-        let item_type: ItemType = match name {
-            "薬草" => ItemType::Consumable,
-            "棍棒" => ItemType::Weapon,
-            "最初の鍵" => ItemType::Key,
-            _ => panic!(),
+    pub fn new(name: &str) -> Result<Item, EngineError> {
+        let (item_type, message_id) = match name {
+            "薬草" => (ItemType::Consumable, "item.herb"),
+            "棍棒" => (ItemType::Weapon, "item.club"),
+            "最初の鍵" => (ItemType::Key, "item.first_key"),
+            _ => return Err(EngineError::UnknownItem(name.to_string())),
         };
-        let description = match name {
-            "薬草" => "HPを30回復",
-            "棍棒" => "粗悪な武器",
-            "最初の鍵" => "簡単な鍵を開けることができる",
-            _ => "",
-        }
-        .to_string();
 
         // TODO
         // RPGSharedState ではなく、Stateを受け取れるように変更(なんかすごい効果を持ったアイテムを実装できるように）
         fn consume_func(item: &Item, rpg_shared_state: &mut RPGSharedState) {
-            match &item.item_type {
-                ItemType::Consumable => match item.name.as_str() {
-                    "薬草" => {
-                        rpg_shared_state.characters[0].current_hp = rpg_shared_state.characters[0]
-                            .max_hp
-                            .min(rpg_shared_state.characters[0].current_hp + 30);
-                    }
-                    _ => {}
-                },
-                _ => return,
+            if let ItemType::Consumable = &item.item_type {
+                if item.name.as_str() == "薬草" {
+                    rpg_shared_state.characters[0].current_hp = rpg_shared_state.characters[0]
+                        .max_hp
+                        .min(rpg_shared_state.characters[0].current_hp + 30);
+                }
             }
         }
-        Item {
+        Ok(Item {
             name: name.to_string(),
             item_type,
             consume_func,
-            description,
-        }
+            message_id,
+        })
+    }
+
+    pub fn display_name(&self, catalog: &Catalog) -> String {
+        catalog.text(&format!("{}.name", self.message_id))
+    }
+
+    pub fn description(&self, catalog: &Catalog) -> String {
+        catalog.text(&format!("{}.description", self.message_id))
     }
 }
 
+#[derive(Clone)]
 pub enum ItemType {
     Weapon,
     Key,
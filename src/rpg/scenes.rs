@@ -3,3 +3,9 @@ pub mod event;
 pub mod field;
 pub mod menu;
 pub mod title;
+
+pub const TITLE_SCENE: &str = "title";
+pub const EVENT_SCENE: &str = "event";
+pub const FIELD_SCENE: &str = "field";
+pub const BATTLE_SCENE: &str = "battle";
+pub const MENU_SCENE: &str = "menu";
@@ -1,154 +1,156 @@
-use crate::engine::application_types::SceneType::RPGBattle;
 use crate::engine::application_types::StateType;
 use crate::engine::choice::ChoiceSetting;
+use crate::engine::error::EngineError;
 use crate::engine::input::Input;
 use crate::engine::scene::Scene;
 use crate::engine::state::State;
 use crate::features::animation::{Animation, AnimationSpan};
 use crate::rpg::mechanism::choice_kind::ChoiceKind;
 use crate::rpg::mechanism::choice_kind::ChoiceKind::Root;
+use crate::rpg::scenes::{FIELD_SCENE, TITLE_SCENE};
+use crate::svg::backend::Element;
 use crate::svg::element_wrapper::ElementWrapper;
 use crate::svg::svg_renderer::{RendererController, SvgRenderer};
-use rand::{thread_rng, Rng};
-use web_sys::Element;
+use rand::Rng;
 
 struct BattleElements {
     max_hp_bar: Element,
     current_hp_bar: Element,
+    // 攻撃を受けた時に揺らす
+    shake: Element,
 }
 pub struct BattleState {
+    own_element: ElementWrapper,
     renderer_controller: RendererController,
     elements: BattleElements,
 }
 
 impl BattleState {
-    pub fn create_battle_scene(shared_state: &mut State) -> Scene {
+    pub fn create_battle_scene(shared_state: &mut State) -> BattleState {
         let document = &shared_state.elements.document;
         let elements = BattleElements {
-            max_hp_bar: document.query_selector("#max-hp-bar").unwrap().unwrap(),
-            current_hp_bar: document.query_selector("#current-hp-bar").unwrap().unwrap(),
+            max_hp_bar: document.query_selector("#max-hp-bar").unwrap(),
+            current_hp_bar: document.query_selector("#current-hp-bar").unwrap(),
+            shake: document.get_element_by_id("battle-shake").unwrap(),
         };
-        let battle_state = BattleState {
+        BattleState {
+            own_element: ElementWrapper::new(document.get_element_by_id("battle").unwrap()),
             renderer_controller: RendererController {
-                renderers: vec![SvgRenderer::new(Root, "battle".to_string(), 40.0)],
+                renderers: vec![SvgRenderer::new(
+                    document,
+                    &shared_state.catalog,
+                    Root,
+                    "battle".to_string(),
+                    40.0,
+                )],
                 choice_tree: ChoiceSetting::get_battle_setting().get_battle_choice_tree(),
                 confirm_index: None,
             },
             elements,
-        };
-        let consume_func = battle_state.create_consume_func();
-        let init_func = battle_state.create_init_func();
-        let scene_type = RPGBattle(battle_state);
-        Scene {
-            own_element: ElementWrapper::new(
-                shared_state
-                    .elements
-                    .document
-                    .get_element_by_id("battle")
-                    .unwrap(),
-            ),
-            scene_type,
-            is_partial_scene: false,
-            consume_func,
-            init_func,
-            update_map_func: Scene::create_update_map_func_empty(),
-            consume_channel_message_func: Scene::create_consume_channel_message_func_empty(),
         }
     }
+}
 
-    pub fn create_init_func(&self) -> fn(&mut Scene, &mut State) {
-        fn init_func(scene: &mut Scene, shared_state: &mut State) {
-            scene.show();
-            if let Scene {
-                scene_type: RPGBattle(battle_state),
-                ..
-            } = scene
-            {
-                battle_state.renderer_controller.initial_render();
-                if let StateType::RPGShared(rpg_shared_state) = &shared_state.state_type {
-                    let character = &rpg_shared_state.characters[0];
-                    let hp_percentage = character.current_hp as f64 / character.max_hp as f64;
-                    let max_hp_bar_width: f64 = battle_state
-                        .elements
-                        .max_hp_bar
-                        .get_attribute("width")
-                        .unwrap()
-                        .parse()
-                        .unwrap();
+impl Scene for BattleState {
+    fn own_element(&self) -> &ElementWrapper {
+        &self.own_element
+    }
+    fn next_scene_ids(&self) -> Vec<&'static str> {
+        vec![FIELD_SCENE, TITLE_SCENE]
+    }
+    fn init(&mut self, shared_state: &mut State) {
+        self.show();
+        self.renderer_controller.initial_render();
+        if let StateType::RPGShared(rpg_shared_state) = &shared_state.state_type {
+            let character = &rpg_shared_state.characters[0];
+            let hp_percentage = character.current_hp as f64 / character.max_hp as f64;
+            // 幅の無い HP バーでは残りの HP を表示せずに戦闘を続ける
+            let max_hp_bar_width = self
+                .elements
+                .max_hp_bar
+                .get_attribute("width")
+                .and_then(|width| width.parse::<f64>().ok());
+            match max_hp_bar_width {
+                Some(max_hp_bar_width) => {
                     let current_hp_bar_width = max_hp_bar_width * hp_percentage;
-                    battle_state
-                        .elements
+                    self.elements
                         .current_hp_bar
-                        .set_attribute("width", &*current_hp_bar_width.to_string())
-                        .unwrap();
-                    shared_state
-                        .interrupt_animations
-                        .push(vec![Animation::create_message(
-                            "ピエンが現れた！".to_string(),
-                        )]);
+                        .set_attribute("width", &current_hp_bar_width.to_string());
                 }
+                None => shared_state
+                    .errors
+                    .push(EngineError::MissingElement("max-hp-bar[width]".to_string())),
             }
+            shared_state
+                .interrupt_animations
+                .push(vec![Animation::create_message(
+                    &shared_state.elements,
+                    shared_state.catalog.borrow().text("battle.appeared"),
+                )]);
         }
-
-        init_func
     }
-    pub fn create_consume_func(&self) -> fn(&mut Scene, &mut State, Input) {
-        fn consume_func(scene: &mut Scene, shared_state: &mut State, input: Input) {
-            if let RPGBattle(battle_state) = &mut scene.scene_type {
-                let BattleState {
-                    renderer_controller,
-                    ..
-                } = battle_state;
-                match input {
-                    // 矢印キーは状態に応じてカーソルを動かすのみ
-                    Input::ArrowUp | Input::ArrowDown | Input::ArrowRight | Input::ArrowLeft => {
-                        renderer_controller.delegate_input(input);
-                        return;
-                    }
-                    _ => {}
-                }
-                if let Input::Enter = input {
-                    // 決定キーが押された場合、まず choice_tree の状態を先に進める
-                    renderer_controller.delegate_enter();
+    fn consume_input(&mut self, shared_state: &mut State, input: Input) {
+        let renderer_controller = &mut self.renderer_controller;
+        match input {
+            // 矢印キーは状態に応じてカーソルを動かすのみ
+            Input::ArrowUp | Input::ArrowDown | Input::ArrowRight | Input::ArrowLeft => {
+                renderer_controller.delegate_input(input);
+                return;
+            }
+            _ => {}
+        }
+        if let Input::Enter = input {
+            // 決定キーが押された場合、まず choice_tree の状態を先に進める
+            renderer_controller.delegate_enter();
 
-                    // 先に進めた choice_tree の状態に応じて、画面を更新
-                    // 後続処理がないなら return
-                    match renderer_controller.now_choice_kind() {
-                        ChoiceKind::Battle => {
-                            shared_state.primitives.requested_scene_index = 0;
-                            shared_state.interrupt_animations.push(vec![
-                                Animation::create_multi_line_messages(vec![
-                                    "もう戦えない！".to_owned(),
-                                    "".to_owned(),
-                                    "目の前が真っ暗になった…".to_owned(),
-                                ]),
-                                Animation::create_fade_out_in_with_span(
-                                    AnimationSpan::FadeOutInLong,
-                                ),
-                            ]);
-                            renderer_controller.close_all();
-                            return;
-                        }
-                        ChoiceKind::Escape => {
-                            if thread_rng().gen_bool(0.7_f64) {
-                                shared_state.primitives.requested_scene_index -= 1;
-                                shared_state.interrupt_animations.push(vec![
-                                    Animation::create_message("逃げ出した".to_string()),
-                                    Animation::create_fade_out_in(),
-                                ]);
-                                renderer_controller.close_all();
-                            } else {
-                                shared_state.interrupt_animations.push(vec![
-                                    Animation::create_message("逃げられなかった！".to_string()),
-                                ]);
-                                renderer_controller.undo_choice_tree();
-                            }
-                        }
-                        _ => {}
+            // 先に進めた choice_tree の状態に応じて、画面を更新
+            // 後続処理がないなら return
+            match renderer_controller.now_choice_kind() {
+                ChoiceKind::Battle => {
+                    shared_state
+                        .interrupt_animations
+                        .push(vec![Animation::create_shake(
+                            &shared_state.elements,
+                            self.elements.shake.clone(),
+                            12,
+                            400,
+                        )]);
+                    shared_state.interrupt_animations.push(vec![
+                        Animation::create_multi_line_messages(
+                            &shared_state.elements,
+                            shared_state.catalog.borrow().lines("battle.defeated"),
+                        ),
+                        Animation::create_fade_out_in_with_span(
+                            &shared_state.elements,
+                            AnimationSpan::FadeOutInLong,
+                        )
+                        .on_midpoint(|state| state.primitives.request_scene(TITLE_SCENE)),
+                    ]);
+                    renderer_controller.close_all();
+                }
+                ChoiceKind::Escape => {
+                    if shared_state.rng.random_bool(0.7_f64) {
+                        shared_state.interrupt_animations.push(vec![
+                            Animation::create_message(
+                                &shared_state.elements,
+                                shared_state.catalog.borrow().text("battle.escaped"),
+                            ),
+                            Animation::create_fade_out_in(&shared_state.elements)
+                                .on_midpoint(|state| state.primitives.request_scene(FIELD_SCENE)),
+                        ]);
+                        renderer_controller.close_all();
+                    } else {
+                        shared_state
+                            .interrupt_animations
+                            .push(vec![Animation::create_message(
+                                &shared_state.elements,
+                                shared_state.catalog.borrow().text("battle.escape_failed"),
+                            )]);
+                        renderer_controller.undo_choice_tree();
                     }
                 }
+                _ => {}
             }
         }
-        consume_func
     }
 }
@@ -1,72 +1,88 @@
-use crate::engine::application_types::SceneType::RPGEvent;
 use crate::engine::application_types::StateType::RPGShared;
+use crate::engine::error::EngineError;
 use crate::engine::input::Input;
 use crate::engine::scene::Scene;
-use crate::engine::state::State;
+use crate::engine::state::{SceneTransition, State};
 use crate::features::animation::Animation;
+use crate::rpg::mechanism::event_script::EventScripts;
+use crate::rpg::scenes::{BATTLE_SCENE, FIELD_SCENE};
 use crate::svg::element_wrapper::ElementWrapper;
-use wasm_bindgen_test::console_log;
+use crate::utils::console_log;
 
-pub struct EventState {}
+// ニューゲーム時に実行するイベント
+pub const OPENING_EVENT: &str = "opening";
+
+// スクリプトの change_scene で切り替えてよいのもここに宣言したシーンだけ
+// (登録されていることは SceneRegistry::validate が確かめる)
+const NEXT_SCENE_IDS: [&str; 2] = [FIELD_SCENE, BATTLE_SCENE];
+
+pub struct EventState {
+    own_element: ElementWrapper,
+    scripts: EventScripts,
+}
 
 impl EventState {
-    pub fn create_event_scene(shared_state: &mut State) -> Scene {
-        let event_state = EventState {};
-        let consume_func = event_state.create_consume_func();
-        let init_func = event_state.create_init_func();
-        let scene_type = RPGEvent(event_state);
-        Scene {
-            own_element: ElementWrapper::new(
-                shared_state
-                    .elements
-                    .document
-                    .get_element_by_id("event")
-                    .unwrap(),
+    pub fn create_event_scene(shared_state: &mut State) -> EventState {
+        let own_element = ElementWrapper::new(
+            shared_state
+                .elements
+                .document
+                .get_element_by_id("event")
+                .unwrap(),
+        );
+        EventState {
+            own_element,
+            scripts: EventScripts::from_json(
+                include_str!("../events.json"),
+                &NEXT_SCENE_IDS,
+                shared_state,
             ),
-            scene_type,
-            is_partial_scene: false,
-            consume_func,
-            init_func,
-            update_map_func: Scene::create_update_map_func_empty(),
-            consume_channel_message_func: Scene::create_consume_channel_message_func_empty(),
         }
     }
-    pub fn create_init_func(&self) -> fn(&mut Scene, &mut State) {
-        fn init_func(scene: &mut Scene, shared_state: &mut State) {
-            console_log!("init event scene");
-            scene.show();
-            match &mut scene.scene_type {
-                RPGEvent(..) => {}
-                _ => panic!(),
-            }
-            shared_state.primitives.requested_scene_index = 2;
-            shared_state.interrupt_animations.push(vec![
-                Animation::create_multi_line_messages(vec![
-                    "SVG QUEST へようこそ！".to_string(),
-                    "".to_string(),
-                    "ここは本来オープニングの画面ですが、".to_string(),
-                    "まだ用意がありません。".to_string(),
-                    "それではごゆっくりお楽しみください。".to_string(),
-                ]),
-                Animation::create_fade_out_in(),
-            ]);
-            if let State {
-                state_type: RPGShared(rpg_shared_state),
-                ..
-            } = shared_state
-            {
-                let opening_end_flag = rpg_shared_state.characters[0].event_flags.get(0);
-                if opening_end_flag.is_some() {
-                    rpg_shared_state.characters[0].event_flags[0] = true;
-                } else {
-                    rpg_shared_state.characters[0].event_flags.push(true);
-                }
-            }
-        }
-        init_func
+}
+
+impl Scene for EventState {
+    fn own_element(&self) -> &ElementWrapper {
+        &self.own_element
     }
-    pub fn create_consume_func(&self) -> fn(&mut Scene, &mut State, Input) {
-        fn consume_func(_: &mut Scene, _: &mut State, _: Input) {}
-        consume_func
+    fn next_scene_ids(&self) -> Vec<&'static str> {
+        NEXT_SCENE_IDS.to_vec()
+    }
+    fn init(&mut self, shared_state: &mut State) {
+        console_log!("init event scene");
+        self.show();
+        let event_id = match &mut shared_state.state_type {
+            RPGShared(rpg_shared_state) => Ok(rpg_shared_state
+                .requested_event
+                .take()
+                .unwrap_or(OPENING_EVENT.to_string())),
+            _ => Err(EngineError::UnsupportedStateType(
+                "the event scene needs the RPG state".to_string(),
+            )),
+        };
+        let mut animations = vec![];
+        let result = event_id
+            .and_then(|event_id| self.scripts.get(&event_id))
+            .and_then(|commands| EventScripts::run(commands, shared_state, &mut animations));
+        if let Err(error) = result {
+            shared_state.report_error(error);
+        }
+        // スクリプトがシーンを指定しなければフィールドに戻る
+        // シーン遷移・マップ移動はどちらも、メッセージを表示し終えて画面が暗くなったところで反映させる
+        let primitives = &mut shared_state.primitives;
+        let transition = primitives
+            .requested_scene_transition
+            .take()
+            .unwrap_or(SceneTransition::Switch(FIELD_SCENE.to_string()));
+        let map_index =
+            std::mem::replace(&mut primitives.requested_map_index, primitives.map_index);
+        animations.push(
+            Animation::create_fade_out_in(&shared_state.elements).on_midpoint(move |state| {
+                state.primitives.requested_map_index = map_index;
+                state.primitives.requested_scene_transition = Some(transition.clone());
+            }),
+        );
+        shared_state.interrupt_animations.push(animations);
     }
+    fn consume_input(&mut self, _: &mut State, _: Input) {}
 }
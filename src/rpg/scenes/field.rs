@@ -1,177 +1,179 @@
-use crate::engine::application_types::SceneType::RPGField;
 use crate::engine::application_types::StateType;
+use crate::engine::catalog::Catalog;
+use crate::engine::error::EngineError;
+use crate::engine::event_bus::GameEvent;
 use crate::engine::input::Input;
 use crate::engine::scene::Scene;
-use crate::engine::state::{Primitives, References, State};
+use crate::engine::state::{Primitives, State};
+use crate::features::animation::{Animation, AnimationSpan};
+use crate::features::camera::{Bounds, Camera};
+#[cfg(feature = "emote")]
 use crate::features::emote::EmoteMessage;
+use crate::features::timeline::Timeline;
+#[cfg(feature = "online")]
 use crate::features::websocket::{ChannelMessage, MessageType};
 use crate::rpg::mechanism::item::Item;
 use crate::rpg::scenes::field::EventType::*;
+use crate::rpg::scenes::{BATTLE_SCENE, EVENT_SCENE, MENU_SCENE};
 use crate::rpg::RPGSharedState;
+use crate::svg::backend::{DomBackend, Element};
 use crate::svg::element_wrapper::ElementWrapper;
 use crate::svg::{Position, SharedElements};
-use crate::Animation;
+#[cfg(feature = "online")]
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::rc::Rc;
-use web_sys::{Document, Element};
+
+// FieldState::create_field_scene で用意するマップの数
+pub const MAP_COUNT: usize = 4;
+// メニューなどを上に積んでいる間のフィールドの不透明度
+const PAUSED_OPACITY: &str = "0.5";
 
 pub struct FieldState {
+    own_element: ElementWrapper,
     character_direction_element: Element,
-    wrapper_element: Element,
-    wrapper_translate_x: i32,
-    wrapper_translate_y: i32,
+    character_element: Element,
     maps: Vec<Map>,
+    catalog: Rc<RefCell<Catalog>>,
+    // 最後に押した矢印の向き (Context で調べるのはこの向きの隣のマス)
+    facing: Input,
+    // Context を押したまま歩くと走る
+    dashing: bool,
 }
 
 impl FieldState {
-    pub fn create_field_scene(shared_state: &mut State) -> Scene {
+    // マップに置く道具が作れなければ登録に失敗する
+    pub fn create_field_scene(shared_state: &mut State) -> Result<FieldState, EngineError> {
         if let State {
             state_type: StateType::RPGShared(rpg_shared_state),
             elements,
             ..
         } = shared_state
         {
-            let mut map = Map::init_1();
+            let mut map = Map::init_1()?;
             map.init_treasure_box_opened(rpg_shared_state);
             map.draw(rpg_shared_state, elements);
-            let character_direction_element = shared_state
-                .elements
-                .document
-                .query_selector(".character.direction")
-                .unwrap()
-                .unwrap();
-            let mut field_state = FieldState {
-                character_direction_element,
-                wrapper_element: shared_state
-                    .elements
-                    .document
-                    .query_selector("#field-wrapper")
-                    .unwrap()
-                    .unwrap(),
-                wrapper_translate_x: 0,
-                wrapper_translate_y: 0,
-                maps: vec![map, Map::init_2(), Map::init_3(), Map::init_4()],
+            let maps: [Map; MAP_COUNT] = [map, Map::init_2()?, Map::init_3()?, Map::init_4()];
+            let document = &shared_state.elements.document;
+            let find = |selectors: &str| {
+                document.query_selector(selectors).ok_or_else(|| {
+                    EngineError::MissingElement(selectors.trim_start_matches('#').to_string())
+                })
             };
-            let consume_func = field_state.create_consume_func();
-            let init_func = field_state.create_init_func();
-            let update_map_func = field_state.create_update_map_func();
-            let consume_channel_message_func = field_state.create_consume_channel_message_func();
-            let scene_type = RPGField(field_state);
-            Scene {
-                own_element: ElementWrapper::new(
-                    shared_state
-                        .elements
-                        .document
-                        .get_element_by_id("field")
-                        .unwrap(),
-                ),
-                scene_type,
-                is_partial_scene: false,
-                consume_func,
-                init_func,
-                update_map_func,
-                consume_channel_message_func,
-            }
+            Ok(FieldState {
+                own_element: ElementWrapper::new(find("#field")?),
+                character_direction_element: find(".character.direction")?,
+                character_element: find("#field-character")?,
+                maps: Vec::from(maps),
+                catalog: shared_state.catalog.clone(),
+                facing: Input::ArrowDown,
+                dashing: false,
+            })
         } else {
-            panic!()
+            Err(EngineError::UnsupportedStateType(
+                "the field scene needs the RPG state".to_string(),
+            ))
         }
     }
+    // 歩き始めたら true (オンライン時の位置は歩き終えてから送る)
     pub fn move_to(
         &mut self,
         rpg_shared_state: &mut RPGSharedState,
         elements: &mut SharedElements,
         primitives: &mut Primitives,
-        _: Rc<RefCell<References>>,
-        interrupt_animations: &mut Vec<Vec<Animation>>,
+        emitted_events: &mut Vec<GameEvent>,
+        interrupt_animations: &mut Timeline,
         input: Input,
-    ) {
-        let map = &mut self.maps[primitives.map_index];
-        // let start_x: i32 = characters[0].position.x;
-        // let start_y: i32 = characters[0].position.y;
-        let mut x: i32 = rpg_shared_state.characters[0].position.x.to_owned();
-        let mut y: i32 = rpg_shared_state.characters[0].position.y.to_owned();
-        let original_translate_x = self.wrapper_translate_x.to_owned();
-        let original_translate_y = self.wrapper_translate_y.to_owned();
-        match input {
-            Input::ArrowUp => {
-                y -= 40;
-                self.wrapper_translate_y += 40;
-            }
-            Input::ArrowDown => {
-                y += 40;
-                self.wrapper_translate_y -= 40;
-            }
-            Input::ArrowRight => {
-                x += 40;
-                self.wrapper_translate_x -= 40;
-            }
-
-            Input::ArrowLeft => {
-                x -= 40;
-                self.wrapper_translate_x += 40;
-            }
-            _ => panic!(),
+    ) -> bool {
+        // 矢印以外では動かない
+        if !matches!(
+            input,
+            Input::ArrowUp | Input::ArrowDown | Input::ArrowRight | Input::ArrowLeft
+        ) {
+            return false;
         }
+        let catalog = self.catalog.clone();
+        let catalog = &catalog.borrow();
+        let character_element = self.character_element.clone();
+        let span = if self.dashing {
+            AnimationSpan::Dash
+        } else {
+            AnimationSpan::Walk
+        };
+        let map = &mut self.maps[primitives.map_index];
+        let start_position = rpg_shared_state.characters[0].position;
+        let Position { x, y } = FieldState::next_position(start_position, &input);
         let found_event = map
             .event_positions
             .iter()
             .enumerate()
             .find(|(_, event)| event.0.x == x && event.0.y == y);
         if found_event.is_none() {
-            match input {
-                Input::ArrowUp | Input::ArrowDown | Input::ArrowRight | Input::ArrowLeft => {
-                    rpg_shared_state.characters[0].position = Position::new(x, y);
-                    self.update_character_position(x, y);
-                    // self.character_position = Position::new(x, y);
-                    // shared_state.interrupt_animations.push(vec![Animation::create_move(start_x, start_y, x, y)]);
-                }
-                _ => panic!(),
-            }
-            return;
+            rpg_shared_state.characters[0].position = Position::new(x, y);
+            let walk = Animation::create_move(
+                elements,
+                &rpg_shared_state.camera,
+                &character_element,
+                start_position,
+                Position::new(x, y),
+                span,
+            );
+            #[cfg(feature = "online")]
+            let walk = walk.on_complete(move |state| state.send_own_position(Some(input.clone())));
+            interrupt_animations.push(vec![walk]);
+            return true;
         }
         let (event_index, found_event) = found_event.unwrap();
         match found_event.1.clone() {
             Gate(key_name) => {
                 if !key_name.is_empty() {
-                    let has_key = rpg_shared_state.characters[0]
+                    let key = rpg_shared_state.characters[0]
                         .inventory
                         .iter()
-                        .find(|item| item.name == key_name)
-                        .is_some();
-                    if has_key {
-                        interrupt_animations.push(vec![Animation::create_message(format!(
-                            "{}を使用した",
-                            key_name
-                        ))]);
+                        .find(|item| item.name == key_name);
+                    if let Some(key) = key {
+                        interrupt_animations.push(vec![Animation::create_message(
+                            elements,
+                            catalog
+                                .format("field.item_used", &[("item", &key.display_name(catalog))]),
+                        )]);
                         map.event_positions.remove(event_index);
                         map.draw(rpg_shared_state, elements);
-                        return;
+                        interrupt_animations
+                            .push(vec![rpg_shared_state.camera.shake(elements, 6, 300)]);
+                        emitted_events.push(GameEvent::GateOpened {
+                            key_name,
+                            map_index: primitives.map_index,
+                        });
+                        return false;
                     } else {
                         interrupt_animations.push(vec![Animation::create_message(
-                            "鍵がかかっている".to_string(),
+                            elements,
+                            catalog.text("field.locked"),
                         )]);
-                        self.reset_translate(original_translate_x, original_translate_y);
-                        return;
+                        return false;
                     }
                 } else {
                     // ただの扉
                 }
             }
+            Person(event_id) => {
+                rpg_shared_state.requested_event = Some(event_id);
+                primitives.request_scene(EVENT_SCENE);
+            }
             Enemy => {
-                primitives.requested_scene_index += 1;
-                interrupt_animations.push(vec![Animation::create_fade_out_in()]);
-                self.reset_translate(original_translate_x, original_translate_y);
-                return;
+                emitted_events.push(GameEvent::BattleStarted {
+                    map_index: primitives.map_index,
+                });
+                interrupt_animations.push(vec![Animation::create_fade_out_in(elements)
+                    .on_midpoint(|state| state.primitives.request_scene(BATTLE_SCENE))]);
+                return false;
             }
             TreasureBox(key_name) => {
                 let treasure_events = map
                     .event_positions
                     .iter()
-                    .filter(|(_, event_type)| match event_type {
-                        TreasureBox(..) => true,
-                        _ => false,
-                    })
+                    .filter(|(_, event_type)| matches!(event_type, TreasureBox(..)))
                     .collect::<Vec<&(Position, EventType)>>();
                 let found_treasure_box = treasure_events
                     .iter()
@@ -183,276 +185,360 @@ impl FieldState {
                     .find(|index| **index == treasure_index)
                     .is_some();
                 if opened {
-                    self.reset_translate(original_translate_x, original_translate_y);
-                    return;
+                    return false;
                 }
                 if !key_name.is_empty() {
-                    let has_key = rpg_shared_state.characters[0]
+                    let key = rpg_shared_state.characters[0]
                         .inventory
                         .iter()
-                        .find(|item| item.name == key_name)
-                        .is_some();
-                    if has_key {
-                        interrupt_animations.push(vec![Animation::create_message(format!(
-                            "{}を使用した",
-                            key_name
-                        ))]);
+                        .find(|item| item.name == key_name);
+                    if let Some(key) = key {
+                        interrupt_animations.push(vec![Animation::create_message(
+                            elements,
+                            catalog
+                                .format("field.item_used", &[("item", &key.display_name(catalog))]),
+                        )]);
                     } else {
                         interrupt_animations.push(vec![Animation::create_message(
-                            "鍵がかかっている".to_string(),
+                            elements,
+                            catalog.text("field.locked"),
                         )]);
-                        self.reset_translate(original_translate_x, original_translate_y);
-                        return;
+                        return false;
                     }
                 }
+                let Some(item) = map.treasure_items.get(treasure_index) else {
+                    return false;
+                };
                 rpg_shared_state.treasure_box_opened[map.map_index].push(treasure_index);
-                map.treasure_elements[treasure_index]
-                    .set_attribute("fill", "gray")
-                    .unwrap();
-                let item = map.treasure_items.get(treasure_index).unwrap();
-                rpg_shared_state.characters[0]
-                    .inventory
-                    .push(Item::new(&item.name));
-                interrupt_animations.push(vec![Animation::create_message(format!(
-                    "{}を手に入れた",
-                    item.name
-                ))]);
-                self.reset_translate(original_translate_x, original_translate_y);
-                return;
+                map.treasure_elements[treasure_index].set_attribute("fill", "gray");
+                rpg_shared_state.characters[0].inventory.push(item.clone());
+                interrupt_animations.push(vec![Animation::create_message(
+                    elements,
+                    catalog.format(
+                        "field.item_acquired",
+                        &[("item", &item.display_name(catalog))],
+                    ),
+                )]);
+                emitted_events.push(GameEvent::ItemAcquired {
+                    item_name: item.name.to_owned(),
+                    map_index: primitives.map_index,
+                });
+                return false;
             }
             Obstacle(..) => {
-                self.reset_translate(original_translate_x, original_translate_y);
-                return;
+                return false;
             }
             MapConnection(map_connection_detail) => {
-                self.update_character_position(x, y);
-                rpg_shared_state.characters[0].position = Position::new(
+                let to_position = Position::new(
                     map_connection_detail.to_position.x,
                     map_connection_detail.to_position.y,
                 );
-                self.reset_translate(original_translate_x, original_translate_y);
-                primitives.requested_map_index =
+                let to_map_index =
                     (primitives.map_index as i32 + map_connection_detail.index_addition) as usize;
-                interrupt_animations.push(vec![Animation::create_fade_out_in()]);
-                return;
+                // 出入口まで歩いてから、画面が真っ暗になったところでマップを切り替える
+                interrupt_animations.push(vec![
+                    Animation::create_move(
+                        elements,
+                        &rpg_shared_state.camera,
+                        &character_element,
+                        start_position,
+                        Position::new(x, y),
+                        span,
+                    ),
+                    Animation::create_fade_out_in(elements).on_midpoint(move |state| {
+                        if let StateType::RPGShared(rpg_shared_state) = &mut state.state_type {
+                            rpg_shared_state.characters[0].position = to_position;
+                        }
+                        state.primitives.requested_map_index = to_map_index;
+                        #[cfg(feature = "online")]
+                        state.send_own_position(Some(input.clone()));
+                    }),
+                ]);
+                return true;
             }
         }
+        false
     }
 
-    pub fn reset_translate(&mut self, original_x: i32, original_y: i32) {
-        self.wrapper_translate_x = original_x;
-        self.wrapper_translate_y = original_y;
-    }
-
-    pub fn update_character_position(&mut self, x: i32, y: i32) {
-        self.wrapper_translate_x = 360 - x;
-        self.wrapper_translate_y = 280 - y;
-        self.wrapper_element
-            .set_attribute(
-                "transform",
-                format!(
-                    "translate({}, {})",
-                    self.wrapper_translate_x, self.wrapper_translate_y
-                )
-                .as_str(),
-            )
-            .unwrap();
-    }
-    pub fn create_init_func(&self) -> fn(&mut Scene, &mut State) {
-        fn init_func(scene: &mut Scene, shared_state: &mut State) {
-            scene.show();
-            if let State {
-                state_type: StateType::RPGShared(rpg_shared_state),
-                primitives,
+    // 向いている方向の隣のマスを調べる (歩かずに話しかけたり宝箱を開けたりする)
+    // 何も無ければ何もしない (走るために押しただけの時にメッセージを出さない)
+    pub fn examine(
+        &mut self,
+        rpg_shared_state: &mut RPGSharedState,
+        elements: &mut SharedElements,
+        primitives: &mut Primitives,
+        emitted_events: &mut Vec<GameEvent>,
+        interrupt_animations: &mut Timeline,
+    ) {
+        let Position { x, y } =
+            FieldState::next_position(rpg_shared_state.characters[0].position, &self.facing);
+        let found_event = self.maps[primitives.map_index]
+            .event_positions
+            .iter()
+            .find(|event| event.0.x == x && event.0.y == y);
+        // 隣のマスに入ろうとした時と同じ (これらのイベントでは歩かない)
+        if let Some((_, Gate(..) | Person(..) | Enemy | TreasureBox(..))) = found_event {
+            self.move_to(
+                rpg_shared_state,
                 elements,
-                ..
-            } = shared_state
-            {
-                match &mut scene.scene_type {
-                    RPGField(field_state) => {
-                        field_state.maps[primitives.map_index].draw(rpg_shared_state, elements);
-                        field_state.update_character_position(
-                            rpg_shared_state.characters[0].position.x,
-                            rpg_shared_state.characters[0].position.y,
-                        );
-                    }
-                    _ => {}
-                }
-
-                if rpg_shared_state.characters[0].position.x == -1
-                    && rpg_shared_state.characters[0].position.y == -1
-                {
-                    rpg_shared_state.characters[0].position = Position::new(360, 280);
-                }
-                shared_state.send_own_position(None);
-            }
-        }
-        init_func
-    }
-    pub fn create_consume_func(&self) -> fn(&mut Scene, &mut State, Input) {
-        fn consume_func(scene: &mut Scene, shared_state: &mut State, input: Input) {
-            if let State {
-                state_type: StateType::RPGShared(rpg_shared_state),
                 primitives,
-                elements,
-                references,
+                emitted_events,
                 interrupt_animations,
-                ..
-            } = shared_state
-            {
-                match &mut scene.scene_type {
-                    RPGField(field_state) => {
-                        let direction_string = match input {
-                            Input::ArrowUp => "↑",
-                            Input::ArrowDown => "↓",
-                            Input::ArrowRight => "→",
-                            Input::ArrowLeft => "←",
-                            _ => "",
-                        };
-                        if direction_string != "" {
-                            field_state
-                                .character_direction_element
-                                .set_inner_html(direction_string);
-                        }
-                        match input {
-                            Input::ArrowUp
-                            | Input::ArrowDown
-                            | Input::ArrowRight
-                            | Input::ArrowLeft => {
-                                field_state.move_to(
-                                    rpg_shared_state,
-                                    elements,
-                                    primitives,
-                                    references.clone(),
-                                    interrupt_animations,
-                                    input.clone(),
-                                );
-                                shared_state.send_own_position(Some(input.clone()));
-                            }
-                            Input::Cancel => {
-                                primitives.requested_scene_index += 2;
-                            }
-                            _ => (),
-                        }
-                    }
-                    _ => panic!(),
-                }
-            }
+                self.facing.clone(),
+            );
         }
-        consume_func
     }
-    pub fn create_update_map_func(&mut self) -> fn(&mut Scene, &mut State) {
-        fn update_map_func(scene: &mut Scene, shared_state: &mut State) {
-            if let State {
-                state_type: StateType::RPGShared(rpg_shared_state),
-                primitives,
-                elements,
-                ..
-            } = shared_state
-            {
-                if let RPGField(field_state) = &mut scene.scene_type {
-                    let map = &mut field_state.maps[primitives.map_index];
-                    map.init_treasure_box_opened(rpg_shared_state);
-                    map.draw(rpg_shared_state, elements);
-                    field_state.update_character_position(
-                        rpg_shared_state.characters[0].position.x,
-                        rpg_shared_state.characters[0].position.y,
-                    );
-                }
-            }
+
+    fn next_position(position: Position, direction: &Input) -> Position {
+        match direction {
+            Input::ArrowUp => Position::new(position.x, position.y - 40),
+            Input::ArrowDown => Position::new(position.x, position.y + 40),
+            Input::ArrowRight => Position::new(position.x + 40, position.y),
+            Input::ArrowLeft => Position::new(position.x - 40, position.y),
+            _ => position,
         }
-        update_map_func
     }
+
+    // map_index のマップで position に立てるか (地面の上で、障害物の無いマス)
+    // 地面と障害物は変わらないので、マップの初期配置で確かめる
+    #[cfg(feature = "dev-console")]
+    pub fn is_walkable(map_index: usize, position: Position) -> Result<bool, EngineError> {
+        let map = match map_index {
+            0 => Map::init_1()?,
+            1 => Map::init_2()?,
+            2 => Map::init_3()?,
+            3 => Map::init_4(),
+            _ => return Ok(false),
+        };
+        Ok(map.is_walkable(position))
+    }
+
+    // キャラクターを position に置き、カメラを合わせる
+    pub fn update_character_position(&mut self, camera: &mut Camera, position: Position) {
+        self.character_element.set_attribute(
+            "transform",
+            format!("translate({}, {})", position.x, position.y).as_str(),
+        );
+        camera.follow(position);
+    }
+    #[cfg(feature = "emote")]
     pub fn consume_emote_message(&mut self, message: EmoteMessage, shared_state: &mut State) {
         if shared_state.primitives.map_index != message.map_index {
             return;
         }
         let own_emote = shared_state.user_name == message.user_name;
+        let parent_element = if own_emote {
+            Some(self.character_element.clone())
+        } else {
+            shared_state
+                .elements
+                .document
+                .get_element_by_id("field-wrapper")
+        };
+        let Some(parent_element) = parent_element else {
+            shared_state.report_error(EngineError::MissingElement("field-wrapper".to_string()));
+            return;
+        };
         shared_state
             .interrupt_animations
-            .push(vec![Animation::show_emote(message, own_emote)]);
+            .push(vec![Animation::show_emote(
+                &shared_state.elements,
+                parent_element,
+                message,
+                own_emote,
+            )]);
     }
+}
 
-    pub fn create_consume_channel_message_func(
-        &mut self,
-    ) -> fn(&mut Scene, &mut State, message: &ChannelMessage) {
-        fn consume_channel_message(
-            scene: &mut Scene,
-            shared_state: &mut State,
-            message: &ChannelMessage,
-        ) {
-            if let Scene {
-                scene_type: RPGField(field_state),
-                ..
-            } = scene
+impl Scene for FieldState {
+    fn own_element(&self) -> &ElementWrapper {
+        &self.own_element
+    }
+    fn next_scene_ids(&self) -> Vec<&'static str> {
+        vec![BATTLE_SCENE, MENU_SCENE, EVENT_SCENE]
+    }
+    fn init(&mut self, shared_state: &mut State) {
+        self.show();
+        // 一時停止したままタイトルに戻った場合も元の明るさで表示する
+        self.own_element.element.set_attribute("opacity", "1");
+        if let State {
+            state_type: StateType::RPGShared(rpg_shared_state),
+            primitives,
+            elements,
+            ..
+        } = shared_state
+        {
+            let map = &mut self.maps[primitives.map_index];
+            map.draw(rpg_shared_state, elements);
+            rpg_shared_state.camera.set_bounds(Some(map.bounds()));
+            self.update_character_position(
+                &mut rpg_shared_state.camera,
+                rpg_shared_state.characters[0].position,
+            );
+
+            if rpg_shared_state.characters[0].position.x == -1
+                && rpg_shared_state.characters[0].position.y == -1
             {
-                if let Ok(emote_message) = serde_json::from_str::<EmoteMessage>(&message.message) {
-                    field_state.consume_emote_message(emote_message, shared_state);
-                    return;
+                rpg_shared_state.characters[0].position = Position::new(360, 280);
+            }
+            #[cfg(feature = "online")]
+            shared_state.send_own_position(None);
+        }
+    }
+    fn consume_input(&mut self, shared_state: &mut State, input: Input) {
+        if let State {
+            state_type: StateType::RPGShared(rpg_shared_state),
+            primitives,
+            elements,
+            interrupt_animations,
+            emitted_events,
+            held_inputs,
+            ..
+        } = shared_state
+        {
+            let direction_string = match input {
+                Input::ArrowUp => "↑",
+                Input::ArrowDown => "↓",
+                Input::ArrowRight => "→",
+                Input::ArrowLeft => "←",
+                _ => "",
+            };
+            if !direction_string.is_empty() {
+                self.character_direction_element
+                    .set_inner_html(direction_string);
+            }
+            match input {
+                Input::ArrowUp | Input::ArrowDown | Input::ArrowRight | Input::ArrowLeft => {
+                    self.facing = input.clone();
+                    self.dashing = held_inputs.contains(&Input::Context);
+                    let walked = self.move_to(
+                        rpg_shared_state,
+                        elements,
+                        primitives,
+                        emitted_events,
+                        interrupt_animations,
+                        input.clone(),
+                    );
+                    // 歩かなかった時も向きは変わるので送る
+                    if !walked {
+                        #[cfg(feature = "online")]
+                        shared_state.send_own_position(Some(input.clone()));
+                    }
                 }
-                if message.user_name == shared_state.user_name {
-                    return;
+                Input::Cancel => {
+                    primitives.push_scene(MENU_SCENE);
                 }
-                if let State {
-                    state_type: StateType::RPGShared(rpg_shared_state),
-                    primitives,
-                    elements,
-                    ..
-                } = shared_state
-                {
-                    let found = rpg_shared_state
-                        .online_users
-                        .iter_mut()
-                        .enumerate()
-                        .find(|(_, user)| user.user_name == message.user_name);
-                    match message.message_type {
-                        MessageType::Left => {
-                            if found.is_some() {
-                                let remove_index = found.unwrap().0;
-                                rpg_shared_state.online_users.remove(remove_index);
-                            }
-                        }
-                        MessageType::Message => {
-                            if let Ok(online_user) =
-                                serde_json::from_str::<PositionMessage>(&message.message)
-                            {
-                                if found.is_some() {
-                                    let found = found.unwrap().1;
-                                    found.map_index = online_user.map_index;
-                                    found.direction = online_user.direction;
-                                    found.position_x = online_user.position_x;
-                                    found.position_y = online_user.position_y;
-                                } else {
-                                    rpg_shared_state.online_users.push(online_user);
-                                }
-                            } else if let Ok(message) =
-                                serde_json::from_str::<ChannelMessage>(&message.message)
-                            {
-                                match message.message_type {
-                                    MessageType::Left => {
-                                        if found.is_some() {
-                                            let remove_index = found.unwrap().0;
-                                            rpg_shared_state.online_users.remove(remove_index);
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            };
-                        }
-                        _ => {}
+                Input::Context => {
+                    self.examine(
+                        rpg_shared_state,
+                        elements,
+                        primitives,
+                        emitted_events,
+                        interrupt_animations,
+                    );
+                }
+                _ => (),
+            }
+        }
+    }
+    fn on_map_update(&mut self, shared_state: &mut State) {
+        if let State {
+            state_type: StateType::RPGShared(rpg_shared_state),
+            primitives,
+            elements,
+            ..
+        } = shared_state
+        {
+            let map = &mut self.maps[primitives.map_index];
+            map.init_treasure_box_opened(rpg_shared_state);
+            map.draw(rpg_shared_state, elements);
+            rpg_shared_state.camera.set_bounds(Some(map.bounds()));
+            self.update_character_position(
+                &mut rpg_shared_state.camera,
+                rpg_shared_state.characters[0].position,
+            );
+        }
+    }
+    fn pause(&mut self, _: &mut State) {
+        self.own_element
+            .element
+            .set_attribute("opacity", PAUSED_OPACITY);
+    }
+    // 上のシーンで道具を使ったり位置を変えたりしていても反映されるよう、描画し直す
+    fn resume(&mut self, shared_state: &mut State) {
+        self.own_element.element.set_attribute("opacity", "1");
+        self.on_map_update(shared_state);
+    }
+    #[cfg(feature = "online")]
+    fn on_channel_message(&mut self, shared_state: &mut State, message: &ChannelMessage) {
+        #[cfg(feature = "emote")]
+        {
+            if let Ok(emote_message) = serde_json::from_str::<EmoteMessage>(&message.message) {
+                self.consume_emote_message(emote_message, shared_state);
+                return;
+            }
+        }
+        if message.user_name == shared_state.user_name {
+            return;
+        }
+        if let State {
+            state_type: StateType::RPGShared(rpg_shared_state),
+            primitives,
+            elements,
+            emitted_events,
+            ..
+        } = shared_state
+        {
+            let found = rpg_shared_state
+                .online_users
+                .iter_mut()
+                .enumerate()
+                .find(|(_, user)| user.user_name == message.user_name);
+            match message.message_type {
+                MessageType::Left => {
+                    if let Some((remove_index, _)) = found {
+                        let left_user = rpg_shared_state.online_users.remove(remove_index);
+                        emitted_events.push(GameEvent::PlayerLeft {
+                            user_name: left_user.user_name,
+                        });
                     }
-                    field_state.maps[primitives.map_index].draw(rpg_shared_state, elements);
-                    // Joinの分は rpg_shared_state 使用の後に持ってこないと、second immutable borrow でビルド失敗する
-                    match message.message_type {
-                        MessageType::Join => {
-                            shared_state.send_own_position(None);
+                }
+                MessageType::Message => {
+                    if let Ok(online_user) =
+                        serde_json::from_str::<PositionMessage>(&message.message)
+                    {
+                        if let Some((_, found)) = found {
+                            found.map_index = online_user.map_index;
+                            found.direction = online_user.direction;
+                            found.position_x = online_user.position_x;
+                            found.position_y = online_user.position_y;
+                        } else {
+                            rpg_shared_state.online_users.push(online_user);
                         }
-                        _ => {}
-                    }
+                    } else if let Ok(message) =
+                        serde_json::from_str::<ChannelMessage>(&message.message)
+                    {
+                        if let MessageType::Left = message.message_type {
+                            if let Some((remove_index, _)) = found {
+                                let left_user = rpg_shared_state.online_users.remove(remove_index);
+                                emitted_events.push(GameEvent::PlayerLeft {
+                                    user_name: left_user.user_name,
+                                });
+                            }
+                        }
+                    };
                 }
+                _ => {}
+            }
+            self.maps[primitives.map_index].draw(rpg_shared_state, elements);
+            // Joinの分は rpg_shared_state 使用の後に持ってこないと、second immutable borrow でビルド失敗する
+            if let MessageType::Join = message.message_type {
+                shared_state.emit(GameEvent::PlayerJoined {
+                    user_name: message.user_name.to_owned(),
+                });
+                shared_state.send_own_position(None);
             }
         }
-        consume_channel_message
     }
 }
 
@@ -481,7 +567,7 @@ impl ObstacleType {
 impl MapConnectionDetail {
     fn inverse(&self) -> MapConnectionDetail {
         MapConnectionDetail {
-            index_addition: self.index_addition * -1,
+            index_addition: -self.index_addition,
             from_position: self.to_position,
             to_position: self.from_position,
         }
@@ -499,6 +585,21 @@ struct Map {
 }
 
 impl Map {
+    #[cfg(feature = "dev-console")]
+    fn is_walkable(&self, position: Position) -> bool {
+        let Position { x, y } = self.ground_start_position;
+        let on_ground = (x..=x + self.ground_width - 40).contains(&position.x)
+            && (y..=y + self.ground_height - 40).contains(&position.y);
+        let blocked = self
+            .event_positions
+            .iter()
+            .any(|(event_position, event_type)| {
+                matches!(event_type, Obstacle(..))
+                    && event_position.x == position.x
+                    && event_position.y == position.y
+            });
+        on_ground && !blocked
+    }
     fn extract_events(
         result: &mut Vec<(Position, EventType)>,
         event_type: EventType,
@@ -511,38 +612,31 @@ impl Map {
     }
     pub fn events_to_elements(
         &mut self,
-        document: &Document,
+        document: &Rc<dyn DomBackend>,
         parent: &Element,
-        treasure_box_opened: &Vec<usize>,
+        treasure_box_opened: &[usize],
     ) {
         let mut treasure_elements = vec![];
         let mut treasure_index = 0_usize;
         // TODO
         // 描画順のスマートなコントロール
         for (position, event_type) in self.event_positions.iter() {
-            match event_type {
-                MapConnection(..) => {
-                    let rect = document
-                        .create_element_ns(Option::from("http://www.w3.org/2000/svg"), "rect")
-                        .unwrap();
-                    rect.set_attribute("x", &*position.x.to_string()).unwrap();
-                    rect.set_attribute("y", &*position.y.to_string()).unwrap();
-                    rect.set_attribute("fill", "black").unwrap();
-                    rect.set_attribute("width", "40").unwrap();
-                    rect.set_attribute("height", "40").unwrap();
-                    rect.class_list()
-                        .add_2("_object", "map-connection")
-                        .unwrap();
-                    parent.append_child(&*rect).unwrap();
-                }
-                _ => {}
+            if let MapConnection(..) = event_type {
+                let rect = document.create_element("rect");
+                rect.set_attribute("x", &position.x.to_string());
+                rect.set_attribute("y", &position.y.to_string());
+                rect.set_attribute("fill", "black");
+                rect.set_attribute("width", "40");
+                rect.set_attribute("height", "40");
+                rect.add_class("_object");
+                rect.add_class("map-connection");
+                parent.append_child(&rect);
             }
         }
 
         for (position, event_type) in self.event_positions.iter() {
-            match event_type {
-                MapConnection(..) => continue,
-                _ => {}
+            if let MapConnection(..) = event_type {
+                continue;
             }
             let rect_color = match event_type {
                 TreasureBox(..) => {
@@ -557,6 +651,7 @@ impl Map {
                     }
                 }
                 Enemy => "red",
+                Person(..) => "khaki",
                 Gate(..) => "brown",
                 Obstacle(obstacle_type) => &*obstacle_type.get_color(),
                 _ => "",
@@ -564,6 +659,7 @@ impl Map {
             let inner_html = match event_type {
                 TreasureBox(..) => "宝",
                 Enemy => "敵",
+                Person(..) => "人",
                 Gate(..) => "",
                 Obstacle(..) => "",
                 _ => "",
@@ -571,54 +667,47 @@ impl Map {
             let class_name = match event_type {
                 TreasureBox(..) => "treasure-box",
                 Enemy => "enemy",
+                Person(..) => "person",
                 Gate(..) => "gate",
                 Obstacle(..) => "obstacle",
                 _ => "",
             };
-            let rect = document
-                .create_element_ns(Option::from("http://www.w3.org/2000/svg"), "rect")
-                .unwrap();
-            rect.set_attribute("x", &*position.x.to_string()).unwrap();
-            rect.set_attribute("y", &*position.y.to_string()).unwrap();
-            rect.set_attribute("fill", rect_color).unwrap();
-            rect.set_attribute("width", "40").unwrap();
-            rect.set_attribute("height", "40").unwrap();
-            rect.class_list().add_2("_object", class_name).unwrap();
-            match event_type {
-                Gate(..) => {
-                    rect.set_attribute("stroke", "silver").unwrap();
-                    rect.set_attribute("stroke-width", "2.5").unwrap();
-                }
-                _ => {}
+            let rect = document.create_element("rect");
+            rect.set_attribute("x", &position.x.to_string());
+            rect.set_attribute("y", &position.y.to_string());
+            rect.set_attribute("fill", rect_color);
+            rect.set_attribute("width", "40");
+            rect.set_attribute("height", "40");
+            rect.add_class("_object");
+            rect.add_class(class_name);
+            if let Gate(..) = event_type {
+                rect.set_attribute("stroke", "silver");
+                rect.set_attribute("stroke-width", "2.5");
             }
-            parent.append_child(&*rect).unwrap();
+            parent.append_child(&rect);
             match event_type {
-                TreasureBox(..) | Enemy => {
-                    let text = document
-                        .create_element_ns(Option::from("http://www.w3.org/2000/svg"), "text")
-                        .unwrap();
-                    text.set_attribute("x", &*(position.x + 2).to_string())
-                        .unwrap();
-                    text.set_attribute("y", &*(position.y + 33).to_string())
-                        .unwrap();
-                    text.set_attribute("font-size", "35").unwrap();
-                    text.set_attribute("fill", "black").unwrap();
-                    text.class_list().add_2("direction", class_name).unwrap();
+                TreasureBox(..) | Enemy | Person(..) => {
+                    let text = document.create_element("text");
+                    text.set_attribute("x", &(position.x + 2).to_string());
+                    text.set_attribute("y", &(position.y + 33).to_string());
+                    text.set_attribute("font-size", "35");
+                    text.set_attribute("fill", "black");
+                    text.add_class("direction");
+                    text.add_class(class_name);
                     text.set_inner_html(inner_html);
-                    parent.append_child(&*text).unwrap();
+                    parent.append_child(&text);
                 }
                 _ => {}
             }
-            match event_type {
-                TreasureBox(..) => treasure_elements.push(rect),
-                _ => {}
+            if let TreasureBox(..) = event_type {
+                treasure_elements.push(rect)
             }
         }
 
         self.treasure_elements = treasure_elements
     }
-    fn init_1() -> Map {
-        let treasure_items = vec![Item::new("薬草")];
+    fn init_1() -> Result<Map, EngineError> {
+        let treasure_items = vec![Item::new("薬草")?];
         let map_connection = MapConnectionDetail {
             index_addition: 1,
             from_position: Position::new(120, -40),
@@ -645,6 +734,11 @@ impl Map {
             TreasureBox("".to_string()),
             Position::new_vec(vec![[320, 120]]),
         );
+        Map::extract_events(
+            event_positions,
+            Person("elder".to_string()),
+            vec![Position::new(440, 200)],
+        );
         Map::extract_events(
             event_positions,
             Obstacle(ObstacleType::Rock),
@@ -704,11 +798,11 @@ impl Map {
             ground_height: 6000,
             ground_color: "#996633".to_string(),
         };
-        map
+        Ok(map)
     }
 
-    fn init_2() -> Map {
-        let treasure_items = vec![Item::new("棍棒")];
+    fn init_2() -> Result<Map, EngineError> {
+        let treasure_items = vec![Item::new("棍棒")?];
         let map_connection_detail = MapConnectionDetail {
             index_addition: 1,
             from_position: Position::new(120, -40),
@@ -748,10 +842,10 @@ impl Map {
             ground_height: 280,
             ground_color: "#663300".to_string(),
         };
-        map
+        Ok(map)
     }
-    fn init_3() -> Map {
-        let treasure_items = vec![Item::new("最初の鍵")];
+    fn init_3() -> Result<Map, EngineError> {
+        let treasure_items = vec![Item::new("最初の鍵")?];
         let map_connection_detail = MapConnectionDetail {
             index_addition: 2,
             from_position: Position::new(600, 240),
@@ -797,7 +891,7 @@ impl Map {
             ground_height: 880,
             ground_color: "#663300".to_string(),
         };
-        map
+        Ok(map)
     }
 
     fn init_4() -> Map {
@@ -832,7 +926,7 @@ impl Map {
             ]),
         );
 
-        let map = Map {
+        Map {
             map_index: 3,
             event_positions: event_positions.to_vec(),
             treasure_elements: vec![],
@@ -841,8 +935,7 @@ impl Map {
             ground_width: 320,
             ground_height: 880,
             ground_color: "#663300".to_string(),
-        };
-        map
+        }
     }
     fn init_treasure_box_opened(&mut self, rpg_shared_state: &mut RPGSharedState) {
         let treasure_box_opened = &mut rpg_shared_state.treasure_box_opened;
@@ -850,41 +943,32 @@ impl Map {
             treasure_box_opened.push(vec![]);
         }
     }
+    // カメラはこの範囲の外を映さない
+    fn bounds(&self) -> Bounds {
+        Bounds {
+            x: self.ground_start_position.x as f64,
+            y: self.ground_start_position.y as f64,
+            width: self.ground_width as f64,
+            height: self.ground_height as f64,
+        }
+    }
     fn draw(&mut self, rpg_shared_state: &mut RPGSharedState, elements: &mut SharedElements) {
-        let ref document = elements.document;
-        let wrapper_element = document.query_selector("#field-wrapper").unwrap().unwrap();
-        while {
-            let child = wrapper_element.child_nodes().get(0);
-            if child.is_none() {
-                false
-            } else {
-                wrapper_element.remove_child(&child.unwrap()).unwrap();
-                true
-            }
-        } {}
-        let ground = document
-            .create_element_ns(Option::from("http://www.w3.org/2000/svg"), "rect")
-            .unwrap();
-        ground
-            .set_attribute("x", &*self.ground_start_position.x.to_string())
-            .unwrap();
-        ground
-            .set_attribute("y", &*self.ground_start_position.y.to_string())
-            .unwrap();
-        ground
-            .set_attribute("fill", &*self.ground_color.to_string())
-            .unwrap();
-        ground
-            .set_attribute("width", &*self.ground_width.to_string())
-            .unwrap();
-        ground
-            .set_attribute("height", &*self.ground_height.to_string())
-            .unwrap();
-        wrapper_element.append_child(&*ground).unwrap();
+        let document = &elements.document;
+        let wrapper_element = document.query_selector("#field-wrapper").unwrap();
+        wrapper_element.remove_children();
+        let ground = document.create_element("rect");
+        ground.set_attribute("x", &self.ground_start_position.x.to_string());
+        ground.set_attribute("y", &self.ground_start_position.y.to_string());
+        ground.set_attribute("fill", &self.ground_color.to_string());
+        ground.set_attribute("width", &self.ground_width.to_string());
+        ground.set_attribute("height", &self.ground_height.to_string());
+        wrapper_element.append_child(&ground);
         let treasure_box_opened = &rpg_shared_state.treasure_box_opened[self.map_index];
         self.events_to_elements(document, &wrapper_element, treasure_box_opened);
+        #[cfg(feature = "online")]
         self.draw_online_user(rpg_shared_state, elements);
     }
+    #[cfg(feature = "online")]
     pub fn draw_online_user(
         &mut self,
         rpg_shared_state: &mut RPGSharedState,
@@ -895,35 +979,24 @@ impl Map {
             if user.map_index != map_index {
                 continue;
             }
-            let ref document = elements.document;
-            let wrapper_element = document.query_selector("#field-wrapper").unwrap().unwrap();
-            let rect = document
-                .create_element_ns(Option::from("http://www.w3.org/2000/svg"), "rect")
-                .unwrap();
-            rect.set_attribute("x", &*user.position_x.to_string())
-                .unwrap();
-            rect.set_attribute("y", &*user.position_y.to_string())
-                .unwrap();
-            rect.set_attribute("fill", "white").unwrap();
-            rect.set_attribute("width", "40").unwrap();
-            rect.set_attribute("height", "40").unwrap();
-            rect.class_list()
-                .add_2(
-                    "online-user",
-                    format!("user-name-{}", user.user_name).as_str(),
-                )
-                .unwrap();
-            wrapper_element.append_child(&*rect).unwrap();
-            let text = document
-                .create_element_ns(Option::from("http://www.w3.org/2000/svg"), "text")
-                .unwrap();
-            text.set_attribute("x", &*(user.position_x + 2).to_string())
-                .unwrap();
-            text.set_attribute("y", &*(user.position_y + 33).to_string())
-                .unwrap();
-            text.set_attribute("font-size", "35").unwrap();
-            text.set_attribute("fill", "black").unwrap();
-            text.class_list().add_2("direction", "online-user").unwrap();
+            let document = &elements.document;
+            let wrapper_element = document.query_selector("#field-wrapper").unwrap();
+            let rect = document.create_element("rect");
+            rect.set_attribute("x", &user.position_x.to_string());
+            rect.set_attribute("y", &user.position_y.to_string());
+            rect.set_attribute("fill", "white");
+            rect.set_attribute("width", "40");
+            rect.set_attribute("height", "40");
+            rect.add_class("online-user");
+            rect.add_class(format!("user-name-{}", user.user_name).as_str());
+            wrapper_element.append_child(&rect);
+            let text = document.create_element("text");
+            text.set_attribute("x", &(user.position_x + 2).to_string());
+            text.set_attribute("y", &(user.position_y + 33).to_string());
+            text.set_attribute("font-size", "35");
+            text.set_attribute("fill", "black");
+            text.add_class("direction");
+            text.add_class("online-user");
             text.set_inner_html(match user.direction {
                 Input::ArrowRight => "→",
                 Input::ArrowLeft => "←",
@@ -931,19 +1004,22 @@ impl Map {
                 Input::ArrowDown => "↓",
                 _ => "",
             });
-            wrapper_element.append_child(&*text).unwrap();
+            wrapper_element.append_child(&text);
         }
     }
 }
 #[derive(Clone)]
 enum EventType {
     Enemy,
+    // 話しかけると events.json のスクリプトを実行する
+    Person(String),
     Gate(String),
     TreasureBox(String),
     Obstacle(ObstacleType),
     MapConnection(MapConnectionDetail),
 }
 
+#[cfg(feature = "online")]
 impl State {
     pub fn send_own_position(&mut self, input: Option<Input>) {
         if let State {
@@ -955,11 +1031,7 @@ impl State {
         {
             let message = PositionMessage {
                 user_name: self.user_name.to_owned(),
-                direction: if input.is_none() {
-                    Input::ArrowDown
-                } else {
-                    input.unwrap()
-                },
+                direction: input.unwrap_or(Input::ArrowDown),
                 position_x: rpg_shared_state.characters[0].position.x,
                 position_y: rpg_shared_state.characters[0].position.y,
                 map_index: primitives.requested_map_index,
@@ -969,6 +1041,7 @@ impl State {
     }
 }
 
+#[cfg(feature = "online")]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PositionMessage {
     pub user_name: String,
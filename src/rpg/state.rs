@@ -1,2 +1,4 @@
 pub mod character;
 pub mod rpg_shared_state;
+pub mod snapshot;
+pub mod variables;
@@ -6,5 +6,4 @@ pub struct Character {
     pub max_hp: u32,
     pub position: Position,
     pub inventory: Vec<Item>,
-    pub event_flags: Vec<bool>,
 }
@@ -1,47 +1,84 @@
 use crate::engine::application_types::StateType;
+use crate::engine::error::EngineError;
 use crate::engine::state::State;
+use crate::features::camera::Camera;
+#[cfg(feature = "online")]
 use crate::rpg::scenes::field::PositionMessage;
 use crate::rpg::state::character::Character;
+use crate::rpg::state::variables::Variables;
 use crate::rpg::SaveData;
 
 pub struct RPGSharedState {
     pub treasure_box_opened: Vec<Vec<usize>>,
     pub save_data: SaveData,
+    #[cfg(feature = "online")]
     pub online_users: Vec<PositionMessage>,
     pub to_send_channel_messages: Vec<String>,
     pub characters: Vec<Character>,
+    pub variables: Variables,
+    // イベントシーンで次に実行するスクリプトの id (None ならオープニング)
+    pub requested_event: Option<String>,
+    // フィールドのカメラ (イベントやデバッグ用のコマンドからも動かす)
+    pub camera: Camera,
 }
 
 impl RPGSharedState {
     pub fn update_save_data(shared_state: &mut State) {
         if let StateType::RPGShared(rpg_shared_state) = &mut shared_state.state_type {
+            rpg_shared_state.save_data.variables = Some(rpg_shared_state.variables.clone());
+            // ロード後の乱数列がセーブ時点から続けた場合と一致するよう、シードと引いた数を残す
+            rpg_shared_state.save_data.rng_seed = Some(shared_state.seed);
+            rpg_shared_state.save_data.rng_word_pos = Some(shared_state.rng.get_word_pos());
             rpg_shared_state.save_data.update(
-                &mut rpg_shared_state.characters,
+                &shared_state.elements.document,
+                &rpg_shared_state.characters,
                 &rpg_shared_state.treasure_box_opened,
                 shared_state.primitives.map_index,
+                &shared_state.key_bindings,
             );
         }
     }
-    pub fn load_save_data(shared_state: &mut State) {
+    pub fn load_save_data(shared_state: &mut State) -> Result<(), EngineError> {
+        let mut rng = None;
         if let StateType::RPGShared(rpg_shared_state) = &mut shared_state.state_type {
-            rpg_shared_state
-                .save_data
-                .load(&mut rpg_shared_state.characters, true);
+            rpg_shared_state.save_data.load(
+                &shared_state.elements.document,
+                &mut rpg_shared_state.characters,
+                &mut rpg_shared_state.variables,
+                true,
+            )?;
             rpg_shared_state.treasure_box_opened =
                 rpg_shared_state.save_data.treasure_box_usize.to_vec();
-            shared_state.primitives.map_index =
-                *rpg_shared_state.save_data.map_usize.get(0).unwrap();
-            shared_state.primitives.requested_map_index =
-                *rpg_shared_state.save_data.map_usize.get(0).unwrap();
+            let map_index = rpg_shared_state.save_data.map_index()?;
+            shared_state.primitives.map_index = map_index;
+            shared_state.primitives.requested_map_index = map_index;
+            rng = rpg_shared_state.save_data.rng_seed.map(|seed| {
+                let word_pos = rpg_shared_state.save_data.rng_word_pos.unwrap_or(0);
+                (seed, word_pos)
+            });
+            if let Some(key_bindings) = &rpg_shared_state.save_data.key_bindings {
+                shared_state.key_bindings = key_bindings.clone();
+            }
         }
+        if let Some((seed, word_pos)) = rng {
+            shared_state.restore_rng(seed, word_pos);
+        }
+        Ok(())
     }
-    pub fn new_game(shared_state: &mut State) {
+    pub fn new_game(shared_state: &mut State) -> Result<(), EngineError> {
         if let StateType::RPGShared(rpg_shared_state) = &mut shared_state.state_type {
             let mut new_save_data = SaveData::empty();
-            new_save_data.load(&mut rpg_shared_state.characters, false);
+            new_save_data.load(
+                &shared_state.elements.document,
+                &mut rpg_shared_state.characters,
+                &mut rpg_shared_state.variables,
+                false,
+            )?;
             rpg_shared_state.treasure_box_opened = new_save_data.treasure_box_usize.to_vec();
-            shared_state.primitives.map_index = *new_save_data.map_usize.get(0).unwrap();
-            shared_state.primitives.requested_map_index = *new_save_data.map_usize.get(0).unwrap();
+            let map_index = new_save_data.map_index()?;
+            shared_state.primitives.map_index = map_index;
+            shared_state.primitives.requested_map_index = map_index;
         }
+        Ok(())
     }
 }
@@ -0,0 +1,55 @@
+use crate::engine::application_types::StateType;
+use crate::engine::state::State;
+#[cfg(feature = "online")]
+use crate::rpg::scenes::field::PositionMessage;
+use crate::rpg::state::variables::Variables;
+use crate::svg::Position;
+use serde::Serialize;
+
+// engine.inspect_state() の game に書き出す RPG の状態
+#[derive(Serialize)]
+pub struct RPGSnapshot {
+    pub characters: Vec<CharacterSnapshot>,
+    pub treasure_box_opened: Vec<Vec<usize>>,
+    pub variables: Variables,
+    #[cfg(feature = "online")]
+    pub online_users: Vec<PositionMessage>,
+}
+
+#[derive(Serialize)]
+pub struct CharacterSnapshot {
+    pub current_hp: u32,
+    pub max_hp: u32,
+    pub position: Position,
+    pub inventory: Vec<String>,
+}
+
+impl RPGSnapshot {
+    // マウント時に InspectGame として登録する
+    pub fn inspect(shared_state: &State) -> Option<serde_json::Value> {
+        let StateType::RPGShared(rpg_shared_state) = &shared_state.state_type else {
+            return None;
+        };
+        let snapshot = RPGSnapshot {
+            characters: rpg_shared_state
+                .characters
+                .iter()
+                .map(|character| CharacterSnapshot {
+                    current_hp: character.current_hp,
+                    max_hp: character.max_hp,
+                    position: character.position,
+                    inventory: character
+                        .inventory
+                        .iter()
+                        .map(|item| item.name.clone())
+                        .collect(),
+                })
+                .collect(),
+            treasure_box_opened: rpg_shared_state.treasure_box_opened.to_vec(),
+            variables: rpg_shared_state.variables.clone(),
+            #[cfg(feature = "online")]
+            online_users: rpg_shared_state.online_users.to_vec(),
+        };
+        serde_json::to_value(snapshot).ok()
+    }
+}
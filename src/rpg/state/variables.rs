@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// オープニングを見終えたか (タイトルの「つづきから」でオープニングを飛ばす)
+pub const OPENING_DONE_FLAG: &str = "opening_done";
+
+// 名前付きになる前のセーブデータの event_flags の位置と名前の対応
+// 意味の分かっている位置だけ並べる (それ以外は flag_<位置> として残す)
+const LEGACY_FLAG_NAMES: [&str; 1] = [OPENING_DONE_FLAG];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+}
+
+// イベントのフラグ・変数を名前で引く (セーブデータにもそのまま保存する)
+// 型が違う値を読んだ時は true/false と 1/0 を読み替える
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(transparent)]
+pub struct Variables {
+    values: BTreeMap<String, Value>,
+}
+
+impl Variables {
+    pub fn new() -> Variables {
+        Variables {
+            values: BTreeMap::new(),
+        }
+    }
+
+    // 位置で管理していた古いフラグを名前付きに移す (対応表に無い位置は flag_<位置>)
+    pub fn from_legacy_flags(event_flags: &[bool]) -> Variables {
+        let mut variables = Variables::new();
+        for (index, value) in event_flags.iter().enumerate() {
+            let name = match LEGACY_FLAG_NAMES.get(index) {
+                Some(name) => name.to_string(),
+                None => format!("flag_{}", index),
+            };
+            variables.set_flag(&name, *value);
+        }
+        variables
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    // 設定したことのないフラグは false
+    pub fn flag(&self, name: &str) -> bool {
+        match self.values.get(name) {
+            Some(Value::Bool(value)) => *value,
+            Some(Value::Int(value)) => *value != 0,
+            None => false,
+        }
+    }
+
+    pub fn set_flag(&mut self, name: &str, value: bool) {
+        self.values.insert(name.to_string(), Value::Bool(value));
+    }
+
+    // 設定したことのない変数は 0
+    pub fn int(&self, name: &str) -> i64 {
+        match self.values.get(name) {
+            Some(Value::Int(value)) => *value,
+            Some(Value::Bool(value)) => *value as i64,
+            None => 0,
+        }
+    }
+
+    pub fn set_int(&mut self, name: &str, value: i64) {
+        self.values.insert(name.to_string(), Value::Int(value));
+    }
+
+    // 上限・下限で止める (何度も加えても溢れない)
+    pub fn add_int(&mut self, name: &str, amount: i64) {
+        self.set_int(name, self.int(name).saturating_add(amount));
+    }
+}
@@ -1,26 +1,56 @@
+use crate::engine::error::EngineError;
+use crate::svg::backend::{DomBackend, Element};
 use crate::svg::element_wrapper::ElementWrapper;
-use web_sys::Document;
+use serde::Serialize;
+use std::rc::Rc;
 
+pub mod backend;
 pub mod element_wrapper;
 pub mod svg_renderer;
 
 pub struct SharedElements {
     pub message: ElementWrapper,
-    pub document: Document,
+    // メッセージ窓の 1 行目と 2 行目
+    pub message_lines: [Element; 2],
+    // 続きのメッセージがあることを示す印
+    pub has_continuous_message: Element,
+    // フェードで不透明度を変える幕
+    pub fader: Element,
+    // 一時停止中に表示する (ページに無ければ何も表示しない)
+    pub pause: Option<ElementWrapper>,
+    pub document: Rc<dyn DomBackend>,
 }
 
 impl SharedElements {
-    pub fn new() -> SharedElements {
-        let window = web_sys::window().unwrap();
-        let document = window.document().unwrap();
-        SharedElements {
-            message: ElementWrapper::new(document.get_element_by_id("message").unwrap()),
+    // 描画先に無い要素はどこにも繋がっていない要素で代え (メッセージやフェードが見えないだけで動き続ける)、
+    // 見つからなかった要素をエラーとして返す
+    pub fn new(document: Rc<dyn DomBackend>) -> (SharedElements, Vec<EngineError>) {
+        let mut errors = vec![];
+        let mut find = |selectors: &str, tag_name: &str| {
+            document.query_selector(selectors).unwrap_or_else(|| {
+                errors.push(EngineError::MissingElement(
+                    selectors.trim_start_matches('#').to_string(),
+                ));
+                document.create_element(tag_name)
+            })
+        };
+        let message = ElementWrapper::new(find("#message", "svg"));
+        let message_lines = [find("#message-1", "text"), find("#message-2", "text")];
+        let has_continuous_message = find("#has-continuous-message", "polygon");
+        let fader = find("#fader rect", "rect");
+        let shared_elements = SharedElements {
+            message,
+            message_lines,
+            has_continuous_message,
+            fader,
+            pause: document.get_element_by_id("pause").map(ElementWrapper::new),
             document,
-        }
+        };
+        (shared_elements, errors)
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
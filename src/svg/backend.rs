@@ -0,0 +1,111 @@
+use crate::engine::error::EngineError;
+use crate::svg::backend::memory::MemoryElement;
+use wasm_bindgen::JsCast;
+
+pub mod memory;
+pub mod sandbox;
+pub mod web;
+
+pub const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
+// エンジンが描画・保存のために使うホスト側の機能
+// ブラウザでは web::WebBackend、ブラウザ外では memory::MemoryBackend を使う
+// セッションログの再生では sandbox::SandboxBackend で保存先だけを切り離す
+pub trait DomBackend {
+    fn get_element_by_id(&self, id: &str) -> Option<Element>;
+    fn query_selector(&self, selectors: &str) -> Option<Element>;
+    fn query_selector_all(&self, selectors: &str) -> Vec<Element>;
+    fn create_element(&self, tag_name: &str) -> Element;
+    fn get_storage_item(&self, key: &str) -> Option<String>;
+    fn set_storage_item(&self, key: &str, value: &str);
+    fn remove_storage_item(&self, key: &str);
+}
+
+#[derive(Clone)]
+pub enum Element {
+    Web(web_sys::Element),
+    Memory(MemoryElement),
+}
+
+impl Element {
+    pub fn get_attribute(&self, name: &str) -> Option<String> {
+        match self {
+            Element::Web(element) => element.get_attribute(name),
+            Element::Memory(element) => element.get_attribute(name),
+        }
+    }
+    pub fn set_attribute(&self, name: &str, value: &str) {
+        match self {
+            Element::Web(element) => element.set_attribute(name, value).unwrap(),
+            Element::Memory(element) => element.set_attribute(name, value),
+        }
+    }
+    pub fn inner_html(&self) -> String {
+        match self {
+            Element::Web(element) => element.inner_html(),
+            Element::Memory(element) => element.inner_html(),
+        }
+    }
+    pub fn set_inner_html(&self, html: &str) {
+        match self {
+            Element::Web(element) => element.set_inner_html(html),
+            Element::Memory(element) => element.set_inner_html(html),
+        }
+    }
+    pub fn outer_html(&self) -> String {
+        match self {
+            Element::Web(element) => element.outer_html(),
+            Element::Memory(element) => element.to_markup(),
+        }
+    }
+    // 自身をマークアップから作った要素で置き換える (この Element は以後ツリーから外れる)
+    pub fn replace_with_markup(&self, markup: &str) -> Result<(), EngineError> {
+        match self {
+            Element::Web(element) => {
+                element.set_outer_html(markup);
+                Ok(())
+            }
+            Element::Memory(element) => element.replace_with_markup(markup),
+        }
+    }
+    pub fn add_class(&self, class_name: &str) {
+        match self {
+            Element::Web(element) => element.class_list().add_1(class_name).unwrap(),
+            Element::Memory(element) => element.add_class(class_name),
+        }
+    }
+    pub fn append_child(&self, child: &Element) {
+        match (self, child) {
+            (Element::Web(element), Element::Web(child)) => {
+                element.append_child(child).unwrap();
+            }
+            (Element::Memory(element), Element::Memory(child)) => element.append_child(child),
+            _ => panic!("cannot mix elements of different backends"),
+        }
+    }
+    pub fn remove(&self) {
+        match self {
+            Element::Web(element) => element.remove(),
+            Element::Memory(element) => element.remove(),
+        }
+    }
+    pub fn remove_children(&self) {
+        match self {
+            Element::Web(element) => {
+                while let Some(child) = element.first_child() {
+                    element.remove_child(&child).unwrap();
+                }
+            }
+            Element::Memory(element) => element.remove_children(),
+        }
+    }
+    // 子要素は複製しない (Node.cloneNode(false) 相当)
+    pub fn clone_node(&self) -> Element {
+        match self {
+            Element::Web(element) => {
+                Element::Web(element.clone_node().unwrap().dyn_into().unwrap())
+            }
+            Element::Memory(element) => Element::Memory(element.clone_node()),
+        }
+    }
+}
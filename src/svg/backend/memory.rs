@@ -0,0 +1,620 @@
+use crate::engine::error::EngineError;
+use crate::svg::backend::{DomBackend, Element};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+// ブラウザを使わずに要素の属性とツリー構造だけを記録するバックエンド
+// cargo test などネイティブ環境でエンジンを動かし、描画結果を検証するために使う
+pub struct MemoryBackend {
+    pub root: MemoryElement,
+    storage: RefCell<HashMap<String, String>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> MemoryBackend {
+        MemoryBackend {
+            root: MemoryElement::new("#document"),
+            storage: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // index.html のような SVG 主体のマークアップからツリーを構築する
+    pub fn from_markup(markup: &str) -> Result<MemoryBackend, EngineError> {
+        let backend = MemoryBackend::new();
+        MarkupParser::new(markup).parse_into(&backend.root)?;
+        Ok(backend)
+    }
+
+    pub fn to_markup(&self) -> String {
+        self.root.children_markup()
+    }
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        MemoryBackend::new()
+    }
+}
+
+impl DomBackend for MemoryBackend {
+    fn get_element_by_id(&self, id: &str) -> Option<Element> {
+        self.root
+            .find_first(&|node| node.get_attribute("id").as_deref() == Some(id))
+            .map(Element::Memory)
+    }
+
+    fn query_selector(&self, selectors: &str) -> Option<Element> {
+        let selector = Selector::parse(selectors);
+        self.root
+            .find_first(&|node| selector.matches(node))
+            .map(Element::Memory)
+    }
+
+    fn query_selector_all(&self, selectors: &str) -> Vec<Element> {
+        let selector = Selector::parse(selectors);
+        let mut result = vec![];
+        self.root
+            .collect(&|node| selector.matches(node), &mut result);
+        result.into_iter().map(Element::Memory).collect()
+    }
+
+    fn create_element(&self, tag_name: &str) -> Element {
+        Element::Memory(MemoryElement::new(tag_name))
+    }
+
+    fn get_storage_item(&self, key: &str) -> Option<String> {
+        self.storage.borrow().get(key).cloned()
+    }
+
+    fn set_storage_item(&self, key: &str, value: &str) {
+        self.storage
+            .borrow_mut()
+            .insert(key.to_string(), value.to_string());
+    }
+
+    fn remove_storage_item(&self, key: &str) {
+        self.storage.borrow_mut().remove(key);
+    }
+}
+
+struct MemoryNode {
+    tag_name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<MemoryElement>,
+    parent: Weak<RefCell<MemoryNode>>,
+}
+
+#[derive(Clone)]
+pub struct MemoryElement {
+    node: Rc<RefCell<MemoryNode>>,
+}
+
+impl MemoryElement {
+    pub fn new(tag_name: &str) -> MemoryElement {
+        MemoryElement {
+            node: Rc::new(RefCell::new(MemoryNode {
+                tag_name: tag_name.to_string(),
+                attributes: vec![],
+                text: "".to_string(),
+                children: vec![],
+                parent: Weak::new(),
+            })),
+        }
+    }
+
+    pub fn tag_name(&self) -> String {
+        self.node.borrow().tag_name.to_owned()
+    }
+
+    pub fn get_attribute(&self, name: &str) -> Option<String> {
+        self.node
+            .borrow()
+            .attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.to_owned())
+    }
+
+    pub fn set_attribute(&self, name: &str, value: &str) {
+        let mut node = self.node.borrow_mut();
+        if let Some(attribute) = node.attributes.iter_mut().find(|(key, _)| key == name) {
+            attribute.1 = value.to_string();
+        } else {
+            node.attributes.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    pub fn class_names(&self) -> Vec<String> {
+        self.get_attribute("class")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|class_name| class_name.to_string())
+            .collect()
+    }
+
+    pub fn add_class(&self, class_name: &str) {
+        let mut class_names = self.class_names();
+        if !class_names.iter().any(|name| name == class_name) {
+            class_names.push(class_name.to_string());
+        }
+        self.set_attribute("class", &class_names.join(" "));
+    }
+
+    // 子要素を持つ場合はマークアップとして返す
+    pub fn inner_html(&self) -> String {
+        let node = self.node.borrow();
+        if node.children.is_empty() {
+            node.text.to_owned()
+        } else {
+            drop(node);
+            self.children_markup()
+        }
+    }
+
+    // マークアップは解釈せず、テキストとして保持する
+    pub fn set_inner_html(&self, html: &str) {
+        self.remove_children();
+        self.node.borrow_mut().text = html.to_string();
+    }
+
+    pub fn children(&self) -> Vec<MemoryElement> {
+        self.node.borrow().children.to_vec()
+    }
+
+    pub fn parent_element(&self) -> Option<MemoryElement> {
+        self.node
+            .borrow()
+            .parent
+            .upgrade()
+            .map(|node| MemoryElement { node })
+    }
+
+    pub fn append_child(&self, child: &MemoryElement) {
+        child.remove();
+        child.node.borrow_mut().parent = Rc::downgrade(&self.node);
+        self.node.borrow_mut().children.push(child.clone());
+    }
+
+    pub fn remove(&self) {
+        if let Some(parent) = self.parent_element() {
+            parent
+                .node
+                .borrow_mut()
+                .children
+                .retain(|child| !Rc::ptr_eq(&child.node, &self.node));
+        }
+        self.node.borrow_mut().parent = Weak::new();
+    }
+
+    pub fn remove_children(&self) {
+        for child in self.children() {
+            child.remove();
+        }
+    }
+
+    // 親の中の同じ位置に、マークアップを解釈した要素を差し込んで自身は外れる
+    // 解釈できなければ何も変えない
+    pub fn replace_with_markup(&self, markup: &str) -> Result<(), EngineError> {
+        let parent = match self.parent_element() {
+            Some(parent) => parent,
+            None => return Ok(()),
+        };
+        let fragment = MemoryElement::new("#fragment");
+        MarkupParser::new(markup).parse_into(&fragment)?;
+        let index = parent
+            .children()
+            .iter()
+            .position(|child| Rc::ptr_eq(&child.node, &self.node))
+            .unwrap();
+        self.remove();
+        for (offset, child) in fragment.children().into_iter().enumerate() {
+            child.node.borrow_mut().parent = Rc::downgrade(&parent.node);
+            parent
+                .node
+                .borrow_mut()
+                .children
+                .insert(index + offset, child);
+        }
+        Ok(())
+    }
+
+    pub fn clone_node(&self) -> MemoryElement {
+        let node = self.node.borrow();
+        let cloned = MemoryElement::new(&node.tag_name);
+        cloned.node.borrow_mut().attributes = node.attributes.to_vec();
+        cloned
+    }
+
+    pub fn to_markup(&self) -> String {
+        let node = self.node.borrow();
+        let attributes = node
+            .attributes
+            .iter()
+            .map(|(key, value)| format!(" {}=\"{}\"", key, value))
+            .collect::<String>();
+        drop(node);
+        format!(
+            "<{}{}>{}</{}>",
+            self.tag_name(),
+            attributes,
+            self.inner_html(),
+            self.tag_name()
+        )
+    }
+
+    fn children_markup(&self) -> String {
+        self.children()
+            .iter()
+            .map(|child| child.to_markup())
+            .collect()
+    }
+
+    fn find_first(&self, predicate: &dyn Fn(&MemoryElement) -> bool) -> Option<MemoryElement> {
+        for child in self.children() {
+            if predicate(&child) {
+                return Some(child);
+            }
+            if let Some(found) = child.find_first(predicate) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    fn collect(&self, predicate: &dyn Fn(&MemoryElement) -> bool, result: &mut Vec<MemoryElement>) {
+        for child in self.children() {
+            if predicate(&child) {
+                result.push(child.clone());
+            }
+            child.collect(predicate, result);
+        }
+    }
+}
+
+// "rect.character" や "#fader rect" のような、複合セレクタを子孫結合子で並べた形式のみ対応
+struct Selector {
+    compounds: Vec<CompoundSelector>,
+}
+
+struct CompoundSelector {
+    tag_name: Option<String>,
+    id: Option<String>,
+    class_names: Vec<String>,
+}
+
+impl Selector {
+    fn parse(selectors: &str) -> Selector {
+        Selector {
+            compounds: selectors
+                .split_whitespace()
+                .map(CompoundSelector::parse)
+                .collect(),
+        }
+    }
+
+    fn matches(&self, element: &MemoryElement) -> bool {
+        let Some((last, ancestors)) = self.compounds.split_last() else {
+            return false;
+        };
+        if !last.matches(element) {
+            return false;
+        }
+        let mut current = element.parent_element();
+        for compound in ancestors.iter().rev() {
+            loop {
+                match current {
+                    Some(ancestor) => {
+                        current = ancestor.parent_element();
+                        if compound.matches(&ancestor) {
+                            break;
+                        }
+                    }
+                    None => return false,
+                }
+            }
+        }
+        true
+    }
+}
+
+impl CompoundSelector {
+    fn parse(compound: &str) -> CompoundSelector {
+        let mut selector = CompoundSelector {
+            tag_name: None,
+            id: None,
+            class_names: vec![],
+        };
+        let mut prefix = ' ';
+        let mut token = String::new();
+        for c in compound.chars().chain(std::iter::once('.')) {
+            if c == '.' || c == '#' {
+                if !token.is_empty() {
+                    match prefix {
+                        '.' => selector.class_names.push(token.to_owned()),
+                        '#' => selector.id = Some(token.to_owned()),
+                        _ => selector.tag_name = Some(token.to_owned()),
+                    }
+                }
+                prefix = c;
+                token.clear();
+            } else {
+                token.push(c);
+            }
+        }
+        selector
+    }
+
+    fn matches(&self, element: &MemoryElement) -> bool {
+        if let Some(tag_name) = &self.tag_name {
+            if *tag_name != element.tag_name() {
+                return false;
+            }
+        }
+        if let Some(id) = &self.id {
+            if element.get_attribute("id").as_ref() != Some(id) {
+                return false;
+            }
+        }
+        let class_names = element.class_names();
+        self.class_names
+            .iter()
+            .all(|class_name| class_names.contains(class_name))
+    }
+}
+
+// index.html を読み込める程度の簡易なマークアップパーサ
+struct MarkupParser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> MarkupParser<'a> {
+    const VOID_ELEMENTS: [&'static str; 5] = ["meta", "link", "br", "img", "input"];
+    const RAW_TEXT_ELEMENTS: [&'static str; 2] = ["script", "style"];
+
+    fn new(markup: &'a str) -> MarkupParser<'a> {
+        MarkupParser { rest: markup }
+    }
+
+    fn parse_into(&mut self, root: &MemoryElement) -> Result<(), EngineError> {
+        let mut stack = vec![root.clone()];
+        while !self.rest.is_empty() {
+            if let Some(rest) = self.rest.strip_prefix("<!--") {
+                self.rest = rest.find("-->").map(|end| &rest[end + 3..]).unwrap_or("");
+            } else if self.rest.starts_with("<!") {
+                self.skip_past(">");
+            } else if let Some(rest) = self.rest.strip_prefix("</") {
+                let end = rest.find('>').unwrap_or(rest.len());
+                let tag_name = rest[..end].trim().to_string();
+                self.rest = rest.get(end + 1..).unwrap_or("");
+                // 対応する開始タグまで閉じる
+                if let Some(index) = stack.iter().rposition(|e| e.tag_name() == tag_name) {
+                    if index > 0 {
+                        stack.truncate(index);
+                    }
+                }
+            } else if self.rest.starts_with('<') {
+                let (element, self_closing) = self.parse_start_tag()?;
+                stack.last().unwrap().append_child(&element);
+                let tag_name = element.tag_name();
+                if Self::RAW_TEXT_ELEMENTS.contains(&tag_name.as_str()) {
+                    let close_tag = format!("</{}", tag_name);
+                    let end = self.rest.find(&close_tag).unwrap_or(self.rest.len());
+                    element.set_inner_html(&self.rest[..end]);
+                    self.rest = &self.rest[end..];
+                    self.skip_past(">");
+                } else if !self_closing && !Self::VOID_ELEMENTS.contains(&tag_name.as_str()) {
+                    stack.push(element);
+                }
+            } else {
+                let end = self.rest.find('<').unwrap_or(self.rest.len());
+                let text = self.rest[..end].trim();
+                if !text.is_empty() {
+                    let parent = stack.last().unwrap();
+                    let joined = format!("{}{}", parent.node.borrow().text, text);
+                    parent.node.borrow_mut().text = joined;
+                }
+                self.rest = &self.rest[end..];
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_past(&mut self, pattern: &str) {
+        self.rest = self
+            .rest
+            .find(pattern)
+            .map(|end| &self.rest[end + pattern.len()..])
+            .unwrap_or("");
+    }
+
+    // 属性の値や > が欠けたまま終わっている場合はエラーにする
+    fn parse_start_tag(&mut self) -> Result<(MemoryElement, bool), EngineError> {
+        let mut rest = &self.rest[1..];
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let tag_name = &rest[..name_end];
+        let element = MemoryElement::new(tag_name);
+        rest = &rest[name_end..];
+        let mut self_closing = false;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                return Err(EngineError::InvalidMarkup(format!(
+                    "unterminated start tag `<{}`",
+                    tag_name
+                )));
+            }
+            if let Some(after) = rest.strip_prefix("/>") {
+                self_closing = true;
+                rest = after;
+                break;
+            }
+            if let Some(after) = rest.strip_prefix('>') {
+                rest = after;
+                break;
+            }
+            let key_end = rest
+                .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+                .unwrap_or(rest.len())
+                .max(1);
+            let key = &rest[..key_end];
+            rest = rest[key_end..].trim_start();
+            let mut value = "";
+            if let Some(after) = rest.strip_prefix('=') {
+                let after = after.trim_start();
+                match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let value_end = after[1..].find(quote).ok_or_else(|| {
+                            EngineError::InvalidMarkup(format!(
+                                "unterminated value of attribute `{}`",
+                                key
+                            ))
+                        })? + 1;
+                        value = &after[1..value_end];
+                        rest = &after[value_end + 1..];
+                    }
+                    Some(_) => {
+                        let value_end = after
+                            .find(|c: char| c.is_whitespace() || c == '>')
+                            .unwrap_or(after.len());
+                        value = &after[..value_end];
+                        rest = &after[value_end..];
+                    }
+                    None => {
+                        return Err(EngineError::InvalidMarkup(format!(
+                            "missing value of attribute `{}`",
+                            key
+                        )))
+                    }
+                }
+            }
+            element.set_attribute(key, value);
+        }
+        self.rest = rest;
+        Ok((element, self_closing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(markup: &str) -> MemoryBackend {
+        MemoryBackend::from_markup(markup).unwrap()
+    }
+
+    fn ids(elements: Vec<Element>) -> Vec<String> {
+        elements
+            .iter()
+            .map(|element| element.get_attribute("id").unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn selector_matches_tag_id_and_classes() {
+        let backend = parse(
+            r#"<svg><g id="fader"><rect id="a" class="character blink"/></g><rect id="b" class="character"/></svg>"#,
+        );
+        assert_eq!(ids(backend.query_selector_all("rect")), ["a", "b"]);
+        assert_eq!(
+            ids(backend.query_selector_all("rect.character")),
+            ["a", "b"]
+        );
+        assert_eq!(ids(backend.query_selector_all(".character.blink")), ["a"]);
+        assert_eq!(ids(backend.query_selector_all("rect#b")), ["b"]);
+        assert_eq!(
+            ids(backend.query_selector_all("text.character")),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn selector_matches_descendants() {
+        let backend =
+            parse(r#"<svg id="root"><g id="fader"><g><rect id="a"/></g></g><rect id="b"/></svg>"#);
+        assert_eq!(ids(backend.query_selector_all("#fader rect")), ["a"]);
+        assert_eq!(ids(backend.query_selector_all("#root #fader rect")), ["a"]);
+        assert_eq!(ids(backend.query_selector_all("#root rect")), ["a", "b"]);
+        // 祖先の順序が逆なら一致しない
+        assert!(backend.query_selector("#fader #root rect").is_none());
+    }
+
+    #[test]
+    fn parses_attributes_and_text() {
+        let backend = parse(
+            r#"<!DOCTYPE html><!-- comment --><svg width=800 height='600'><text id="t" x="1" hidden>こんにちは</text><br><rect id="r"/></svg>"#,
+        );
+        let svg = backend.query_selector("svg").unwrap();
+        assert_eq!(svg.get_attribute("width").as_deref(), Some("800"));
+        assert_eq!(svg.get_attribute("height").as_deref(), Some("600"));
+        let text = backend.get_element_by_id("t").unwrap();
+        assert_eq!(text.get_attribute("x").as_deref(), Some("1"));
+        assert_eq!(text.get_attribute("hidden").as_deref(), Some(""));
+        assert_eq!(text.inner_html(), "こんにちは");
+        // br は閉じタグが無くても後ろの要素を子にしない
+        let Some(Element::Memory(rect)) = backend.get_element_by_id("r") else {
+            panic!("rect is missing");
+        };
+        assert_eq!(rect.parent_element().unwrap().tag_name(), "svg");
+    }
+
+    #[test]
+    fn keeps_script_as_raw_text() {
+        let backend = parse(r#"<script>if (a < b) { run("<g>"); }</script><g id="after"></g>"#);
+        let script = backend.query_selector("script").unwrap();
+        assert_eq!(script.inner_html(), r#"if (a < b) { run("<g>"); }"#);
+        assert!(backend.query_selector("script g").is_none());
+        assert!(backend.get_element_by_id("after").is_some());
+    }
+
+    #[test]
+    fn round_trips_markup() {
+        let markup = r#"<svg id="s"><g class="a b"><text x="1">x</text></g></svg>"#;
+        assert_eq!(parse(markup).to_markup(), markup);
+    }
+
+    #[test]
+    fn rejects_truncated_markup() {
+        for markup in [
+            r#"<rect x="#,
+            r#"<rect x=  "#,
+            r#"<rect x="1"#,
+            r#"<rect x='1>"#,
+            "<rect",
+        ] {
+            assert!(
+                matches!(
+                    MemoryBackend::from_markup(markup),
+                    Err(EngineError::InvalidMarkup(_))
+                ),
+                "{}",
+                markup
+            );
+        }
+    }
+
+    #[test]
+    fn replaces_element_in_place() {
+        let backend = parse(r#"<svg><g id="a"></g><g id="b"></g><g id="c"></g></svg>"#);
+        let b = backend.get_element_by_id("b").unwrap();
+        b.replace_with_markup(r#"<g id="b2"><rect/></g>"#).unwrap();
+        assert_eq!(ids(backend.query_selector_all("g")), ["a", "b2", "c"]);
+        // 解釈できないマークアップでは置き換えない
+        let c = backend.get_element_by_id("c").unwrap();
+        assert!(c.replace_with_markup(r#"<g id="c2"#).is_err());
+        assert_eq!(ids(backend.query_selector_all("g")), ["a", "b2", "c"]);
+    }
+
+    #[test]
+    fn stores_items_per_backend() {
+        let backend = MemoryBackend::new();
+        assert_eq!(backend.get_storage_item("save"), None);
+        backend.set_storage_item("save", "{}");
+        assert_eq!(backend.get_storage_item("save").as_deref(), Some("{}"));
+        backend.remove_storage_item("save");
+        assert_eq!(backend.get_storage_item("save"), None);
+    }
+}
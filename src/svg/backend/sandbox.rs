@@ -0,0 +1,70 @@
+use crate::svg::backend::{DomBackend, Element};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// 要素の検索・作成は元のバックエンドに任せ、保存先だけをメモリ上に切り離す
+// セッションログを再生する時に、プレイヤーのセーブデータを読み書きしないために使う
+pub struct SandboxBackend {
+    inner: Rc<dyn DomBackend>,
+    storage: RefCell<HashMap<String, String>>,
+    // true になったら保存先も元のバックエンドに任せる
+    passthrough: Cell<bool>,
+}
+
+impl SandboxBackend {
+    pub fn new(inner: Rc<dyn DomBackend>) -> SandboxBackend {
+        SandboxBackend {
+            inner,
+            storage: RefCell::new(HashMap::new()),
+            passthrough: Cell::new(false),
+        }
+    }
+
+    // 巻き戻しの再生を終えたら、以降の保存は元の保存先に書く
+    pub fn pass_through(&self) {
+        self.passthrough.set(true);
+        self.storage.borrow_mut().clear();
+    }
+}
+
+impl DomBackend for SandboxBackend {
+    fn get_element_by_id(&self, id: &str) -> Option<Element> {
+        self.inner.get_element_by_id(id)
+    }
+
+    fn query_selector(&self, selectors: &str) -> Option<Element> {
+        self.inner.query_selector(selectors)
+    }
+
+    fn query_selector_all(&self, selectors: &str) -> Vec<Element> {
+        self.inner.query_selector_all(selectors)
+    }
+
+    fn create_element(&self, tag_name: &str) -> Element {
+        self.inner.create_element(tag_name)
+    }
+
+    fn get_storage_item(&self, key: &str) -> Option<String> {
+        if self.passthrough.get() {
+            return self.inner.get_storage_item(key);
+        }
+        self.storage.borrow().get(key).cloned()
+    }
+
+    fn set_storage_item(&self, key: &str, value: &str) {
+        if self.passthrough.get() {
+            return self.inner.set_storage_item(key, value);
+        }
+        self.storage
+            .borrow_mut()
+            .insert(key.to_string(), value.to_string());
+    }
+
+    fn remove_storage_item(&self, key: &str) {
+        if self.passthrough.get() {
+            return self.inner.remove_storage_item(key);
+        }
+        self.storage.borrow_mut().remove(key);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::svg::backend::{DomBackend, Element, SVG_NAMESPACE};
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Storage};
+
+pub struct WebBackend {
+    document: Document,
+    // 要素の検索はこの要素の子孫に限る (None ならページ全体)
+    root: Option<web_sys::Element>,
+    // 同じページの複数のエンジンでセーブデータが混ざらないよう、キーの先頭に付ける
+    storage_prefix: String,
+}
+
+impl WebBackend {
+    pub fn new() -> WebBackend {
+        let window = web_sys::window().unwrap();
+        WebBackend {
+            document: window.document().unwrap(),
+            root: None,
+            storage_prefix: String::new(),
+        }
+    }
+
+    // ストレージのキーの接頭辞はルート要素の data-storage-prefix 属性で指定する
+    pub fn with_root(root: web_sys::Element) -> WebBackend {
+        WebBackend {
+            storage_prefix: root
+                .get_attribute("data-storage-prefix")
+                .unwrap_or_default(),
+            root: Some(root),
+            ..WebBackend::new()
+        }
+    }
+
+    fn storage(&self) -> Storage {
+        web_sys::window().unwrap().local_storage().unwrap().unwrap()
+    }
+
+    fn storage_key(&self, key: &str) -> String {
+        format!("{}{}", self.storage_prefix, key)
+    }
+}
+
+impl Default for WebBackend {
+    fn default() -> Self {
+        WebBackend::new()
+    }
+}
+
+impl DomBackend for WebBackend {
+    fn get_element_by_id(&self, id: &str) -> Option<Element> {
+        match &self.root {
+            // 複数のエンジンを置くと同じ id がページ内に重複する
+            Some(_) => self.query_selector(&format!("[id=\"{}\"]", id)),
+            None => self.document.get_element_by_id(id).map(Element::Web),
+        }
+    }
+
+    fn query_selector(&self, selectors: &str) -> Option<Element> {
+        let element = match &self.root {
+            Some(root) => root.query_selector(selectors),
+            None => self.document.query_selector(selectors),
+        };
+        element.unwrap().map(Element::Web)
+    }
+
+    fn query_selector_all(&self, selectors: &str) -> Vec<Element> {
+        let node_list = match &self.root {
+            Some(root) => root.query_selector_all(selectors),
+            None => self.document.query_selector_all(selectors),
+        }
+        .unwrap();
+        let mut elements = vec![];
+        for n in 0..node_list.length() {
+            let element = node_list.item(n).unwrap().dyn_into::<web_sys::Element>();
+            elements.push(Element::Web(element.unwrap()));
+        }
+        elements
+    }
+
+    fn create_element(&self, tag_name: &str) -> Element {
+        Element::Web(
+            self.document
+                .create_element_ns(Some(SVG_NAMESPACE), tag_name)
+                .unwrap(),
+        )
+    }
+
+    fn get_storage_item(&self, key: &str) -> Option<String> {
+        self.storage().get_item(&self.storage_key(key)).unwrap()
+    }
+
+    fn set_storage_item(&self, key: &str, value: &str) {
+        self.storage()
+            .set_item(&self.storage_key(key), value)
+            .unwrap();
+    }
+
+    fn remove_storage_item(&self, key: &str) {
+        self.storage().remove_item(&self.storage_key(key)).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use web_sys::Element;
+use crate::svg::backend::Element;
 
 pub struct ElementWrapper {
     pub element: Element,
@@ -10,9 +10,9 @@ impl ElementWrapper {
     }
 
     pub fn show(&self) {
-        self.element.set_attribute("display", "block").unwrap();
+        self.element.set_attribute("display", "block");
     }
     pub fn hide(&self) {
-        self.element.set_attribute("display", "none").unwrap();
+        self.element.set_attribute("display", "none");
     }
 }
@@ -1,12 +1,19 @@
+use crate::engine::catalog::Catalog;
 use crate::engine::choice::{Choice, ChoiceTree};
 use crate::engine::input::Input;
 use crate::rpg::mechanism::choice_kind::ChoiceKind;
 use crate::rpg::mechanism::choice_kind::ChoiceKind::ChoseNth;
-use web_sys::{Document, Element};
+use crate::svg::backend::{DomBackend, Element};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 pub enum CursorType {
     Default,
+    // 横並びの選択肢用 (いまは使っている画面がない)
+    #[allow(dead_code)]
     Side,
+    // エモートの一覧でしか使わない
+    #[cfg_attr(not(feature = "emote"), allow(dead_code))]
     Box,
 }
 
@@ -23,13 +30,8 @@ pub struct Cursor {
 }
 
 impl Cursor {
-    pub fn empty() -> Cursor {
-        let element = web_sys::window()
-            .unwrap()
-            .document()
-            .unwrap()
-            .create_element_ns(Some("http://www.w3.org/2000/svg"), "text")
-            .unwrap();
+    pub fn empty(document: &Rc<dyn DomBackend>) -> Cursor {
+        let element = document.create_element("text");
         Cursor {
             element,
             chose_index: 0,
@@ -57,7 +59,7 @@ impl Cursor {
         }
     }
     pub fn new(
-        document: &Document,
+        document: &Rc<dyn DomBackend>,
         cursor_id: &str,
         choice_length: usize,
         step_length: f64,
@@ -85,6 +87,7 @@ impl Cursor {
         self.choice_length = choice_length;
         self.chose_index = self.chose_index.min(self.choice_length - 1);
     }
+    #[cfg_attr(not(feature = "emote"), allow(dead_code))]
     pub fn set_box_length(&mut self, x_length: usize, y_length: usize) {
         self.box_x_length = x_length;
         self.box_y_length = y_length;
@@ -96,22 +99,14 @@ impl Cursor {
         self.chose_index = 0;
         match self.cursor_type {
             CursorType::Default => {
-                self.element
-                    .set_attribute("y", &*self.default_y.to_string())
-                    .unwrap();
+                self.element.set_attribute("y", &self.default_y.to_string());
             }
             CursorType::Side => {
-                self.element
-                    .set_attribute("x", &*self.default_x.to_string())
-                    .unwrap();
+                self.element.set_attribute("x", &self.default_x.to_string());
             }
             CursorType::Box => {
-                self.element
-                    .set_attribute("x", &*self.default_x.to_string())
-                    .unwrap();
-                self.element
-                    .set_attribute("y", &*self.default_y.to_string())
-                    .unwrap();
+                self.element.set_attribute("x", &self.default_x.to_string());
+                self.element.set_attribute("y", &self.default_y.to_string());
             }
         }
     }
@@ -151,32 +146,26 @@ impl Cursor {
         match self.cursor_type {
             CursorType::Default => {
                 let new_y: f64 = self.default_y + new_index as f64 * self.step_length;
-                self.element
-                    .set_attribute("y", new_y.to_string().as_str())
-                    .unwrap();
+                self.element.set_attribute("y", new_y.to_string().as_str());
             }
             CursorType::Side => {
                 let new_x: f64 = self.default_x + new_index as f64 * self.step_length;
-                self.element
-                    .set_attribute("x", new_x.to_string().as_str())
-                    .unwrap();
+                self.element.set_attribute("x", new_x.to_string().as_str());
             }
             CursorType::Box => {
                 let (x, y) = self.index_to_box_x_box_y(self.chose_index);
                 let new_x: f64 = self.default_x + x as f64 * self.step_length;
-                self.element
-                    .set_attribute("x", new_x.to_string().as_str())
-                    .unwrap();
+                self.element.set_attribute("x", new_x.to_string().as_str());
                 let new_y: f64 = self.default_y + y as f64 * self.step_length;
-                self.element
-                    .set_attribute("y", new_y.to_string().as_str())
-                    .unwrap();
+                self.element.set_attribute("y", new_y.to_string().as_str());
             }
         }
     }
 }
 
 pub struct SvgRenderer {
+    document: Rc<dyn DomBackend>,
+    catalog: Rc<RefCell<Catalog>>,
     choice_kind: ChoiceKind,
     target_part_name: String,
     wrapper_element: Option<Element>,
@@ -191,15 +180,23 @@ pub struct SvgRenderer {
 }
 
 impl SvgRenderer {
-    pub fn new(choice_kind: ChoiceKind, target_part_name: String, step_length: f64) -> SvgRenderer {
+    pub fn new(
+        document: &Rc<dyn DomBackend>,
+        catalog: &Rc<RefCell<Catalog>>,
+        choice_kind: ChoiceKind,
+        target_part_name: String,
+        step_length: f64,
+    ) -> SvgRenderer {
         let mut renderer = SvgRenderer {
+            document: document.clone(),
+            catalog: catalog.clone(),
             choice_kind,
             target_part_name,
             wrapper_element: None,
             item_element: None,
             message_wrapper_element: None,
             message_element: None,
-            cursor: Cursor::empty(),
+            cursor: Cursor::empty(document),
             step_length,
             item_labels: vec![],
             item_x: 0.0,
@@ -216,21 +213,15 @@ impl SvgRenderer {
         self.load_message_element();
     }
     pub fn load_wrapper_element(&mut self) {
-        self.wrapper_element = web_sys::window()
-            .unwrap()
-            .document()
-            .unwrap()
-            .get_element_by_id(&self.get_wrapper_id())
+        self.wrapper_element = self.document.get_element_by_id(&self.get_wrapper_id())
     }
     pub fn get_wrapper_id(&self) -> String {
         format!("render-{}-wrapper", self.target_part_name)
     }
 
     pub fn load_cursor(&mut self) {
-        let cursor_element = web_sys::window()
-            .unwrap()
-            .document()
-            .unwrap()
+        let cursor_element = self
+            .document
             .get_element_by_id(&self.get_cursor_id())
             .unwrap();
         self.cursor = Cursor::new_with_element(cursor_element, self.step_length);
@@ -241,11 +232,7 @@ impl SvgRenderer {
     }
 
     pub fn load_item_element(&mut self) {
-        self.item_element = web_sys::window()
-            .unwrap()
-            .document()
-            .unwrap()
-            .get_element_by_id(&self.get_item_id());
+        self.item_element = self.document.get_element_by_id(&self.get_item_id());
         if let Some(element) = &self.item_element {
             self.item_x = element.get_attribute("x").unwrap().parse().unwrap();
             self.item_y = element.get_attribute("y").unwrap().parse().unwrap();
@@ -255,10 +242,8 @@ impl SvgRenderer {
         format!("render-{}-item", self.target_part_name)
     }
     pub fn load_message_wrapper_element(&mut self) {
-        self.message_wrapper_element = web_sys::window()
-            .unwrap()
-            .document()
-            .unwrap()
+        self.message_wrapper_element = self
+            .document
             .get_element_by_id(&self.get_message_wrapper_id())
     }
     pub fn get_message_wrapper_id(&self) -> String {
@@ -266,11 +251,7 @@ impl SvgRenderer {
     }
 
     pub fn load_message_element(&mut self) {
-        self.message_element = web_sys::window()
-            .unwrap()
-            .document()
-            .unwrap()
-            .get_element_by_id(&self.get_message_id())
+        self.message_element = self.document.get_element_by_id(&self.get_message_id())
     }
     pub fn get_message_id(&self) -> String {
         format!("render-{}-message", self.target_part_name)
@@ -280,87 +261,73 @@ impl SvgRenderer {
         format!("render-{}-rendered", self.target_part_name)
     }
 
+    // labels と description は文言の id でもよい (Catalog に無ければそのまま表示する)
     pub fn render(&mut self, labels: Vec<String>, description: &str) {
-        self.item_labels = labels;
-        let document = web_sys::window().unwrap().document().unwrap();
-        if let Some(to_remove) = document.get_element_by_id(self.get_rendered_id().as_str()) {
+        let catalog = self.catalog.borrow();
+        self.item_labels = labels.iter().map(|label| catalog.text(label)).collect();
+        let description = catalog.text(description);
+        drop(catalog);
+        if let Some(to_remove) = self
+            .document
+            .get_element_by_id(self.get_rendered_id().as_str())
+        {
             to_remove.remove();
         }
-        let group_element = document
-            .create_element_ns(Some("http://www.w3.org/2000/svg"), "g")
-            .unwrap();
-        group_element
-            .set_attribute("id", self.get_rendered_id().as_str())
-            .unwrap();
+        let group_element = self.document.create_element("g");
+        group_element.set_attribute("id", self.get_rendered_id().as_str());
         if let Some(wrapper_element) = &self.wrapper_element {
-            wrapper_element.append_child(&*group_element).unwrap();
-            wrapper_element.set_attribute("display", "block").unwrap();
+            wrapper_element.append_child(&group_element);
+            wrapper_element.set_attribute("display", "block");
         }
 
         for (index, label) in self.item_labels.iter().enumerate() {
             if let Some(item_element) = &self.item_element {
-                let node = item_element.clone_node().unwrap();
-                let empty_element = document
-                    .create_element_ns(Some("http://www.w3.org/2000/svg"), "text")
-                    .unwrap();
-                node.append_child(&*empty_element).unwrap();
-                let element = empty_element.parent_element().unwrap();
+                let element = item_element.clone_node();
                 element.set_inner_html(label);
                 match self.cursor.cursor_type {
                     CursorType::Default => {
-                        element
-                            .set_attribute("x", &*self.item_x.to_string())
-                            .unwrap();
-                        element
-                            .set_attribute(
-                                "y",
-                                &*(self.item_y + index as f64 * self.step_length).to_string(),
-                            )
-                            .unwrap();
+                        element.set_attribute("x", &self.item_x.to_string());
+                        element.set_attribute(
+                            "y",
+                            &(self.item_y + index as f64 * self.step_length).to_string(),
+                        );
                     }
                     CursorType::Box => {
                         let (x, y) = self.cursor.index_to_box_x_box_y(index);
-                        element
-                            .set_attribute(
-                                "x",
-                                &*(self.item_x + x as f64 * self.step_length).to_string(),
-                            )
-                            .unwrap();
-                        element
-                            .set_attribute(
-                                "y",
-                                &*(self.item_y + y as f64 * self.step_length).to_string(),
-                            )
-                            .unwrap();
+                        element.set_attribute(
+                            "x",
+                            &(self.item_x + x as f64 * self.step_length).to_string(),
+                        );
+                        element.set_attribute(
+                            "y",
+                            &(self.item_y + y as f64 * self.step_length).to_string(),
+                        );
                     }
                     _ => {}
                 }
-                element.set_attribute("display", "block").unwrap();
-                group_element.append_child(&*element).unwrap();
+                element.set_attribute("display", "block");
+                group_element.append_child(&element);
             }
         }
         self.cursor.reset();
-        self.cursor
-            .element
-            .set_attribute("display", "block")
-            .unwrap();
+        self.cursor.element.set_attribute("display", "block");
         if let Some(element) = &self.message_wrapper_element {
             let display = if description.is_empty() {
                 "none"
             } else {
                 "block"
             };
-            element.set_attribute("display", display).unwrap();
+            element.set_attribute("display", display);
         }
         if let Some(element) = &self.message_element {
             if !description.is_empty() {
-                element.set_inner_html(description);
+                element.set_inner_html(&description);
             }
         }
     }
     pub fn hide(&self) {
         if let Some(element) = &self.wrapper_element {
-            element.set_attribute("display", "none").unwrap();
+            element.set_attribute("display", "none");
         }
     }
 }
@@ -418,7 +385,7 @@ impl RendererController {
                         ..
                     },
                     ..,
-                ) = branch.get(0)
+                ) = branch.first()
                 {
                     self.choice_tree.choose(renderer.cursor.chose_index);
                 }
@@ -468,7 +435,7 @@ impl RendererController {
     pub fn get_chose_nth(&self) -> Option<usize> {
         for token in self.choice_tree.chose_kinds.iter() {
             if let ChoseNth(_, index) = token {
-                return index.clone();
+                return *index;
             }
         }
         None
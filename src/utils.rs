@@ -8,3 +8,18 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+// wasm_bindgen_test::console_log! はネイティブ環境では呼び出せないため、ターゲットごとに出力先を切り替える
+macro_rules! console_log {
+    ($($arg:tt)*) => (
+        $crate::utils::log(&format!($($arg)*))
+    )
+}
+pub(crate) use console_log;
+
+pub fn log(message: &str) {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::console_log!("{}", message);
+    #[cfg(not(target_arch = "wasm32"))]
+    println!("{}", message);
+}
@@ -0,0 +1,569 @@
+use rpg_lib::engine::error::EngineError;
+use rpg_lib::engine::session::SessionLog;
+use rpg_lib::engine::Engine;
+use rpg_lib::{
+    create_headless_rpg_engine, create_rpg_engine_with_scenes, replay_headless_rpg_engine,
+    DomBackend, MemoryBackend, Track,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// オープニングを済ませてフィールドの (360, 240) にいるセーブデータ
+const SAVE: &str = r#"{"character_u32":[20,80],"treasure_box_usize":[[]],"map_usize":[0],"map_i32":[360,240],"inventory_string":[],"check_token":0,"variables":{"opening_done":true}}"#;
+
+fn memory_backend() -> Rc<MemoryBackend> {
+    Rc::new(MemoryBackend::from_markup(include_str!("../index.html")).unwrap())
+}
+
+// index.html を読み込んだメモリ上の DOM でエンジンを動かし、書き込まれた属性を確かめる
+fn mount() -> (Rc<MemoryBackend>, Engine) {
+    let backend = memory_backend();
+    let engine = create_headless_rpg_engine(backend.clone(), 1);
+    (backend, engine)
+}
+
+fn advance(engine: &mut Engine, step: &mut f64, frames: usize) {
+    for _ in 0..frames {
+        *step += 16.0;
+        engine.animate(*step);
+    }
+}
+
+fn attribute(backend: &MemoryBackend, selectors: &str, name: &str) -> Option<String> {
+    backend
+        .query_selector(selectors)
+        .and_then(|element| element.get_attribute(name))
+}
+
+fn message(backend: &MemoryBackend) -> String {
+    backend.query_selector("#message-1").unwrap().inner_html()
+}
+
+// タイトルで NEW GAME を選び、オープニングのメッセージを送ってフィールドに出る
+fn start_new_game(backend: &MemoryBackend, engine: &mut Engine, step: &mut f64) {
+    advance(engine, step, 5);
+    assert_eq!(
+        attribute(backend, "#title", "display").as_deref(),
+        Some("block")
+    );
+    engine.keydown("a".to_string());
+    advance(engine, step, 100);
+    assert_eq!(message(backend), "SVG QUEST へようこそ！");
+    for _ in 0..5 {
+        engine.keydown("a".to_string());
+        advance(engine, step, 100);
+    }
+    assert_eq!(
+        attribute(backend, "#title", "display").as_deref(),
+        Some("none")
+    );
+    assert_eq!(
+        attribute(backend, "#field", "display").as_deref(),
+        Some("block")
+    );
+}
+
+#[test]
+fn new_game_reaches_field() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(360, 280)")
+    );
+    // ゲーム側の状態はマウント時に登録された書き出し方で game に入る
+    let snapshot: serde_json::Value = serde_json::from_str(&engine.inspect_state()).unwrap();
+    assert_eq!(snapshot["scene_id"], "field");
+    assert_eq!(
+        snapshot["game"]["characters"][0]["position"],
+        serde_json::json!({"x": 360, "y": 280})
+    );
+}
+
+#[test]
+fn arrow_key_walks_character_and_camera() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    engine.keydown("ArrowLeft".to_string());
+    engine.keyup("ArrowLeft".to_string());
+    advance(&mut engine, &mut step, 30);
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(320, 280)")
+    );
+    assert_eq!(
+        attribute(&backend, "#field-camera", "transform").as_deref(),
+        Some("translate(60, 0) scale(1)")
+    );
+    // 歩き終えた位置から次の 1 マスを映す
+    engine.keydown("ArrowLeft".to_string());
+    engine.keyup("ArrowLeft".to_string());
+    advance(&mut engine, &mut step, 30);
+    assert_eq!(
+        attribute(&backend, "#field-camera", "transform").as_deref(),
+        Some("translate(100, 0) scale(1)")
+    );
+}
+
+// 歩いている間に押したキーは最後の 1 つだけ溜めておき、歩き終えてから処理する
+#[test]
+fn arrow_pressed_while_walking_is_buffered() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    engine.keydown("ArrowLeft".to_string());
+    engine.keyup("ArrowLeft".to_string());
+    let snapshot: serde_json::Value = serde_json::from_str(&engine.inspect_state()).unwrap();
+    assert_eq!(snapshot["to_send_channel_messages"], serde_json::json!([]));
+    advance(&mut engine, &mut step, 3);
+    // 1 歩 (150 ms) のうちに押し直す
+    for key in ["ArrowRight", "ArrowUp"] {
+        engine.keydown(key.to_string());
+        engine.keyup(key.to_string());
+    }
+    let mut sent = vec![];
+    for _ in 0..60 {
+        advance(&mut engine, &mut step, 1);
+        let snapshot: serde_json::Value = serde_json::from_str(&engine.inspect_state()).unwrap();
+        if let Some(messages) = snapshot["to_send_channel_messages"].as_array() {
+            sent.extend(messages.iter().cloned());
+        }
+    }
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(320, 240)")
+    );
+    // オンライン時の位置は歩き終えた時に送る
+    if cfg!(feature = "online") {
+        let positions = sent
+            .iter()
+            .map(|message| {
+                let message: serde_json::Value =
+                    serde_json::from_str(message.as_str().unwrap()).unwrap();
+                (
+                    message["direction"].as_str().unwrap().to_string(),
+                    message["position_x"].as_i64().unwrap(),
+                    message["position_y"].as_i64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            vec![
+                ("ArrowLeft".to_string(), 320, 280),
+                ("ArrowUp".to_string(), 320, 240)
+            ]
+        );
+    }
+}
+
+// 自分のエモートはサーバーから折り返されてから、キャラクターの頭上に出る
+#[cfg(feature = "emote")]
+#[test]
+fn own_emote_is_drawn_over_character() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    // カメラが動いた後でもキャラクターについていく
+    engine.keydown("ArrowLeft".to_string());
+    engine.keyup("ArrowLeft".to_string());
+    advance(&mut engine, &mut step, 30);
+    let snapshot: serde_json::Value = serde_json::from_str(&engine.inspect_state()).unwrap();
+    let user_name = snapshot["user_name"].as_str().unwrap();
+    let emote = serde_json::json!({
+        "user_name": user_name,
+        "position_x": 320,
+        "position_y": 280,
+        "map_index": 0,
+        "emote": "!",
+    });
+    let channel_message = serde_json::json!({
+        "user_name": user_name,
+        "message_type": "Message",
+        "message": emote.to_string(),
+    });
+    engine.receive_message(&channel_message.to_string());
+    advance(&mut engine, &mut step, 3);
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(320, 280)")
+    );
+    let selectors = "#field-character .emote-background.character";
+    assert_eq!(attribute(&backend, selectors, "x").as_deref(), Some("0"));
+    assert_eq!(attribute(&backend, selectors, "y").as_deref(), Some("-50"));
+    assert_eq!(
+        backend
+            .query_selector("#field-character .emote.character")
+            .map(|element| element.inner_html())
+            .as_deref(),
+        Some("!")
+    );
+    // 表示し終えたら消える
+    advance(&mut engine, &mut step, 400);
+    assert!(backend.query_selector(".emote").is_none());
+}
+
+// 押している間に割り当てを変えても、押した時の入力が離される
+#[test]
+fn key_rebound_while_held_is_released() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    engine.keydown("ArrowLeft".to_string());
+    advance(&mut engine, &mut step, 5);
+    engine.bind_key("Cancel".to_string(), "ArrowLeft".to_string());
+    engine.keyup("ArrowLeft".to_string());
+    advance(&mut engine, &mut step, 60);
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(320, 280)")
+    );
+}
+
+// フォーカスが外れて keyup が届かなくても歩き続けない
+#[test]
+fn release_all_keys_stops_walking() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    engine.keydown("ArrowLeft".to_string());
+    advance(&mut engine, &mut step, 5);
+    engine.release_all_keys();
+    advance(&mut engine, &mut step, 60);
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(320, 280)")
+    );
+}
+
+#[test]
+fn menu_is_pushed_over_field() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    engine.keydown("z".to_string());
+    advance(&mut engine, &mut step, 3);
+    assert_eq!(
+        attribute(&backend, "#menu", "display").as_deref(),
+        Some("block")
+    );
+    // 下のフィールドは隠さずに一時停止させる
+    assert_eq!(
+        attribute(&backend, "#field", "display").as_deref(),
+        Some("block")
+    );
+    assert_eq!(
+        attribute(&backend, "#field", "opacity").as_deref(),
+        Some("0.5")
+    );
+    engine.keydown("z".to_string());
+    advance(&mut engine, &mut step, 3);
+    assert_eq!(
+        attribute(&backend, "#menu", "display").as_deref(),
+        Some("none")
+    );
+    assert_eq!(
+        attribute(&backend, "#field", "opacity").as_deref(),
+        Some("1")
+    );
+}
+
+#[test]
+fn context_examines_facing_tile_and_dashes_while_held() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    // 下の隣のマスには何も無いので調べても何も起きず、押したまま歩くと半分の時間で 1 マス進む
+    engine.keydown("x".to_string());
+    engine.keydown("ArrowLeft".to_string());
+    engine.keyup("ArrowLeft".to_string());
+    advance(&mut engine, &mut step, 6);
+    engine.keyup("x".to_string());
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(320, 280)")
+    );
+    // 上に 3 マス歩き、宝箱の前で調べると開ける
+    for _ in 0..3 {
+        engine.keydown("ArrowUp".to_string());
+        engine.keyup("ArrowUp".to_string());
+        advance(&mut engine, &mut step, 30);
+    }
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(320, 160)")
+    );
+    engine.keydown("x".to_string());
+    engine.keyup("x".to_string());
+    advance(&mut engine, &mut step, 30);
+    assert!(message(&backend).ends_with("を手に入れた"));
+}
+
+// 長老に話しかけるとスクリプトが動き、道具を受け取ってフィールドに戻る
+#[test]
+fn talking_to_person_runs_event_script() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    for key in ["ArrowUp", "ArrowUp", "ArrowRight", "ArrowRight"] {
+        engine.keydown(key.to_string());
+        engine.keyup(key.to_string());
+        advance(&mut engine, &mut step, 30);
+    }
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(400, 200)")
+    );
+    assert_eq!(
+        attribute(&backend, "#event", "display").as_deref(),
+        Some("block")
+    );
+    assert!(message(&backend).starts_with("長老「よく来たな。"));
+    // 1 回目は残りの文字を出し、2 回目でメッセージを閉じる
+    for _ in 0..2 {
+        engine.keydown("a".to_string());
+        advance(&mut engine, &mut step, 100);
+    }
+    assert_eq!(
+        attribute(&backend, "#field", "display").as_deref(),
+        Some("block")
+    );
+    assert!(engine.inspect_state().contains(r#""inventory":["薬草"]"#));
+    assert!(engine.inspect_state().contains(r#""elder_met":true"#));
+}
+
+// タイトルは戦闘への遷移を宣言していないので、要求してもタイトルに留まる
+#[test]
+fn undeclared_scene_transition_is_reported() {
+    let backend = memory_backend();
+    let mut engine = create_rpg_engine_with_scenes(backend.clone(), false, Some(1), |_, state| {
+        state.interrupt_animations.play(Track::call(|state| {
+            state.primitives.request_scene("battle")
+        }));
+        Ok(())
+    });
+    let errors = Rc::new(RefCell::new(vec![]));
+    let reported = errors.clone();
+    engine.on_error(move |error| reported.borrow_mut().push(error.clone()));
+    let mut step = 0.0;
+    advance(&mut engine, &mut step, 1);
+    assert!(matches!(
+        errors.borrow()[..],
+        [EngineError::InvalidSceneTransition(_)]
+    ));
+    assert_eq!(
+        attribute(&backend, "#title", "display").as_deref(),
+        Some("block")
+    );
+    assert_eq!(
+        attribute(&backend, "#battle", "display").as_deref(),
+        Some("none")
+    );
+}
+
+// メッセージ窓やフェードの幕が無いページでも、見つからない要素を通知して遊び続けられる
+#[test]
+fn missing_shared_elements_are_reported() {
+    let markup = include_str!("../index.html")
+        .replace(r#"id="fader""#, r#"id="no-fader""#)
+        .replace(r#"id="message-2""#, r#"id="no-message-2""#);
+    let backend = Rc::new(MemoryBackend::from_markup(&markup).unwrap());
+    let mut engine = create_headless_rpg_engine(backend.clone(), 1);
+    let errors = Rc::new(RefCell::new(vec![]));
+    let reported = errors.clone();
+    engine.on_error(move |error| reported.borrow_mut().push(error.clone()));
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    assert_eq!(
+        errors.borrow()[..],
+        [
+            EngineError::MissingElement("message-2".to_string()),
+            EngineError::MissingElement("fader rect".to_string()),
+        ]
+    );
+}
+
+fn export(engine: &Engine) -> SessionLog {
+    SessionLog::from_json(&engine.export_session_log()).unwrap()
+}
+
+#[test]
+fn replay_reproduces_state_without_reading_player_save() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    start_new_game(&backend, &mut engine, &mut step);
+    engine.keydown("ArrowLeft".to_string());
+    engine.keyup("ArrowLeft".to_string());
+    advance(&mut engine, &mut step, 30);
+    engine.keydown("ArrowUp".to_string());
+    advance(&mut engine, &mut step, 60);
+    engine.keyup("ArrowUp".to_string());
+    engine.keydown("z".to_string());
+    advance(&mut engine, &mut step, 3);
+    let session_log = export(&engine);
+    assert_eq!(session_log.initial_save, None);
+
+    // 再生先にあるセーブデータは使わず、書き換えもしない
+    let replay_backend = memory_backend();
+    replay_backend.set_storage_item("save", SAVE);
+    let replayed = replay_headless_rpg_engine(replay_backend.clone(), &session_log).unwrap();
+    assert_eq!(replayed.inspect_state(), engine.inspect_state());
+    assert_eq!(replayed.export_session_log(), engine.export_session_log());
+    assert_eq!(replay_backend.to_markup(), backend.to_markup());
+    assert_eq!(
+        replay_backend.get_storage_item("save").as_deref(),
+        Some(SAVE)
+    );
+}
+
+#[test]
+fn replay_continues_from_recorded_save() {
+    let backend = memory_backend();
+    backend.set_storage_item("save", SAVE);
+    let mut engine = create_headless_rpg_engine(backend.clone(), 1);
+    let mut step = 0.0;
+    continue_game(&mut engine, &mut step);
+    assert_eq!(
+        attribute(&backend, "#field", "display").as_deref(),
+        Some("block")
+    );
+    engine.keydown("ArrowRight".to_string());
+    engine.keyup("ArrowRight".to_string());
+    advance(&mut engine, &mut step, 30);
+    let session_log = export(&engine);
+    assert_eq!(session_log.initial_save.as_deref(), Some(SAVE));
+
+    let replay_backend = memory_backend();
+    let replayed = replay_headless_rpg_engine(replay_backend.clone(), &session_log).unwrap();
+    assert_eq!(replayed.inspect_state(), engine.inspect_state());
+    assert_eq!(replay_backend.to_markup(), backend.to_markup());
+    assert_eq!(replay_backend.get_storage_item("save"), None);
+    assert_eq!(backend.get_storage_item("save").as_deref(), Some(SAVE));
+}
+
+// dev-console ビルドで記録した exec はどのビルドでも読み込める
+#[test]
+fn session_log_with_exec_replays_in_any_build() {
+    let session_log = SessionLog::from_json(
+        r#"{"seed":1,"initial_save":null,"events":[{"Animate":16.0},{"Exec":"teleport 0 360 240"},{"Animate":32.0}]}"#,
+    )
+    .unwrap();
+    assert!(replay_headless_rpg_engine(memory_backend(), &session_log).is_ok());
+}
+
+// 記録と違うセーブデータでは再生を始めない
+#[test]
+fn replay_stops_when_save_differs() {
+    let backend = memory_backend();
+    backend.set_storage_item("save", SAVE);
+    let mut engine = create_headless_rpg_engine(backend, 1);
+    let mut step = 0.0;
+    continue_game(&mut engine, &mut step);
+    let session_log = export(&engine);
+
+    let (_, mut other) = mount();
+    let before = other.inspect_state();
+    assert!(matches!(
+        other.replay(&session_log),
+        Err(EngineError::InvalidSessionLog(_))
+    ));
+    assert_eq!(other.inspect_state(), before);
+}
+
+// タイトルで CONTINUE を選び、セーブデータの位置からフィールドに出る
+fn continue_game(engine: &mut Engine, step: &mut f64) {
+    advance(engine, step, 5);
+    engine.keydown("ArrowDown".to_string());
+    engine.keyup("ArrowDown".to_string());
+    engine.keydown("a".to_string());
+    advance(engine, step, 100);
+}
+
+fn rng_of(engine: &Engine) -> (u64, u128) {
+    let snapshot: serde_json::Value = serde_json::from_str(&engine.inspect_state()).unwrap();
+    (
+        snapshot["seed"].as_u64().unwrap(),
+        snapshot["rng_word_pos"].as_u64().unwrap() as u128,
+    )
+}
+
+// セーブしても乱数を引き直さず、ロードするとセーブした時点の続きから引ける
+#[test]
+fn save_keeps_rng_position() {
+    let backend = memory_backend();
+    backend.set_storage_item("save", SAVE);
+    let mut engine = create_headless_rpg_engine(backend.clone(), 1);
+    let mut step = 0.0;
+    continue_game(&mut engine, &mut step);
+    let before_save = rng_of(&engine);
+    assert_eq!(before_save.0, 1);
+    assert!(before_save.1 > 0);
+    // メニューの SAVE を選んで「はい」で確定する
+    engine.keydown("z".to_string());
+    advance(&mut engine, &mut step, 3);
+    let save_index = if cfg!(feature = "emote") { 4 } else { 2 };
+    for _ in 0..save_index {
+        engine.keydown("ArrowDown".to_string());
+        engine.keyup("ArrowDown".to_string());
+        advance(&mut engine, &mut step, 3);
+    }
+    engine.keydown("a".to_string());
+    advance(&mut engine, &mut step, 3);
+    engine.keydown("a".to_string());
+    advance(&mut engine, &mut step, 3);
+    let save = backend.get_storage_item("save").unwrap();
+    assert_ne!(save, SAVE);
+    assert_eq!(rng_of(&engine), before_save);
+
+    let loaded_backend = memory_backend();
+    loaded_backend.set_storage_item("save", &save);
+    let mut loaded = create_headless_rpg_engine(loaded_backend, 2);
+    let mut step = 0.0;
+    continue_game(&mut loaded, &mut step);
+    assert_eq!(rng_of(&loaded), before_save);
+}
+
+// 何も起きないフレームは最後の 1 つだけ記録するので、放置してもログが伸び続けない
+#[test]
+fn idle_frames_are_coalesced_in_session_log() {
+    let (backend, mut engine) = mount();
+    let mut step = 0.0;
+    advance(&mut engine, &mut step, 5);
+    let before = export(&engine).events.len();
+    advance(&mut engine, &mut step, 1000);
+    assert_eq!(export(&engine).events.len(), before);
+    engine.keydown("a".to_string());
+    advance(&mut engine, &mut step, 100);
+    let session_log = export(&engine);
+    let replayed = replay_headless_rpg_engine(memory_backend(), &session_log).unwrap();
+    assert_eq!(replayed.inspect_state(), engine.inspect_state());
+    assert_eq!(message(&backend), "SVG QUEST へようこそ！");
+}
+
+// 巻き戻しは記録開始時のセーブデータで再生し、プレイヤーのセーブデータは書き換えない
+#[test]
+fn rewind_keeps_player_save() {
+    let backend = memory_backend();
+    backend.set_storage_item("save", SAVE);
+    let mut engine = create_headless_rpg_engine(backend.clone(), 1);
+    let mut step = 0.0;
+    continue_game(&mut engine, &mut step);
+    engine.keydown("ArrowRight".to_string());
+    engine.keyup("ArrowRight".to_string());
+    advance(&mut engine, &mut step, 30);
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(400, 240)")
+    );
+    // 記録を始めた後にセーブされた
+    backend.set_storage_item("save", "later");
+    // ArrowRight を押す直前に戻す
+    engine.rewind(2);
+    assert_eq!(
+        attribute(&backend, "#field-character", "transform").as_deref(),
+        Some("translate(360, 240)")
+    );
+    assert_eq!(backend.get_storage_item("save").as_deref(), Some("later"));
+}